@@ -0,0 +1,24 @@
+-- Tables that existed before migrations were introduced
+CREATE TABLE IF NOT EXISTS messages (
+    id INTEGER PRIMARY KEY,
+    author TEXT NOT NULL,
+    content TEXT NOT NULL,
+    link TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    timestamp REAL NOT NULL,
+    image TEXT
+);
+
+CREATE TABLE IF NOT EXISTS quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote TEXT NOT NULL,
+    author TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request TEXT NOT NULL,
+    user TEXT NOT NULL,
+    votes INTEGER DEFAULT 0
+);
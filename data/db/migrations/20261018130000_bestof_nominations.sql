@@ -0,0 +1,8 @@
+-- Bestofs added by nomination rather than by reaction count
+ALTER TABLE messages ADD COLUMN nominated BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE IF NOT EXISTS bestof_nominations (
+    message_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (message_id, user_id)
+);
@@ -0,0 +1,2 @@
+-- Local copy of the bestof's image, see src/data/archive.rs
+ALTER TABLE messages ADD COLUMN archived_image TEXT;
@@ -0,0 +1,2 @@
+-- Discord user ID of the bestof author, used to look up their current nickname and avatar
+ALTER TABLE messages ADD COLUMN author_id INTEGER;
@@ -0,0 +1,6 @@
+-- NSFW flag and @everyone visibility of each scanned channel
+CREATE TABLE IF NOT EXISTS channel_restrictions (
+    id INTEGER PRIMARY KEY,
+    nsfw BOOLEAN NOT NULL,
+    public BOOLEAN NOT NULL
+);
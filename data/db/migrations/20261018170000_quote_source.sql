@@ -0,0 +1,4 @@
+-- Where a quote came from, when it was saved from a message
+ALTER TABLE quotes ADD COLUMN author_user_id INTEGER;
+ALTER TABLE quotes ADD COLUMN timestamp REAL;
+ALTER TABLE quotes ADD COLUMN source_link TEXT;
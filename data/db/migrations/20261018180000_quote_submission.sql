@@ -0,0 +1,7 @@
+-- Who added a quote, when, and in which server
+ALTER TABLE quotes ADD COLUMN submitted_by INTEGER;
+ALTER TABLE quotes ADD COLUMN created_at REAL;
+ALTER TABLE quotes ADD COLUMN guild_id INTEGER;
+
+-- Every quote so far was added in the main server
+UPDATE quotes SET guild_id = 561602796286378029 WHERE guild_id IS NULL;
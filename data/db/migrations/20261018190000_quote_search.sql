@@ -0,0 +1,22 @@
+-- Full text index over quote text, kept in sync with the quotes table
+CREATE VIRTUAL TABLE IF NOT EXISTS quotes_fts USING fts5(
+    quote,
+    content = 'quotes',
+    content_rowid = 'id'
+);
+
+CREATE TRIGGER IF NOT EXISTS quotes_fts_insert AFTER INSERT ON quotes BEGIN
+    INSERT INTO quotes_fts (rowid, quote) VALUES (new.id, new.quote);
+END;
+
+CREATE TRIGGER IF NOT EXISTS quotes_fts_delete AFTER DELETE ON quotes BEGIN
+    INSERT INTO quotes_fts (quotes_fts, rowid, quote) VALUES ('delete', old.id, old.quote);
+END;
+
+CREATE TRIGGER IF NOT EXISTS quotes_fts_update AFTER UPDATE OF quote ON quotes BEGIN
+    INSERT INTO quotes_fts (quotes_fts, rowid, quote) VALUES ('delete', old.id, old.quote);
+    INSERT INTO quotes_fts (rowid, quote) VALUES (new.id, new.quote);
+END;
+
+-- Index the quotes stored before the index existed
+INSERT INTO quotes_fts (quotes_fts) VALUES ('rebuild');
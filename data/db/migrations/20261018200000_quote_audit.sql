@@ -0,0 +1,18 @@
+-- Every edit or deletion of a quote, with the full row as it was beforehand
+CREATE TABLE IF NOT EXISTS quote_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    quote_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    changed_by INTEGER NOT NULL,
+    changed_at REAL NOT NULL,
+    old_quote TEXT NOT NULL,
+    old_author TEXT NOT NULL,
+    old_author_user_id INTEGER,
+    old_timestamp REAL,
+    old_source_link TEXT,
+    old_submitted_by INTEGER,
+    old_created_at REAL,
+    old_guild_id INTEGER
+);
+
+CREATE INDEX IF NOT EXISTS quote_audit_quote_id ON quote_audit (quote_id);
@@ -0,0 +1,7 @@
+-- Thumbs up (1) or down (-1) on a quote, one per user
+CREATE TABLE IF NOT EXISTS quote_votes (
+    quote_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    vote INTEGER NOT NULL,
+    PRIMARY KEY (quote_id, user_id)
+);
@@ -0,0 +1,11 @@
+-- Quotes posted as the daily quote. Each cycle goes through every quote once before the next
+-- cycle starts.
+CREATE TABLE IF NOT EXISTS daily_quotes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    quote_id INTEGER NOT NULL,
+    cycle INTEGER NOT NULL,
+    posted_at REAL NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS daily_quotes_guild_cycle ON daily_quotes (guild_id, cycle);
@@ -0,0 +1,10 @@
+-- Lines of conversation quotes, each with its own speaker, in the order they were said
+CREATE TABLE IF NOT EXISTS quote_lines (
+    quote_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    speaker TEXT NOT NULL,
+    line TEXT NOT NULL,
+    PRIMARY KEY (quote_id, position)
+);
+
+CREATE INDEX IF NOT EXISTS quote_lines_speaker ON quote_lines (speaker COLLATE NOCASE);
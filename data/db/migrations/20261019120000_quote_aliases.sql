@@ -0,0 +1,10 @@
+-- Names quote authors go by, linked to their Discord user
+CREATE TABLE IF NOT EXISTS quote_aliases (
+    guild_id INTEGER NOT NULL,
+    alias TEXT NOT NULL COLLATE NOCASE,
+    user_id INTEGER NOT NULL,
+    added_by INTEGER NOT NULL,
+    PRIMARY KEY (guild_id, alias)
+);
+
+CREATE INDEX IF NOT EXISTS quote_aliases_user ON quote_aliases (guild_id, user_id);
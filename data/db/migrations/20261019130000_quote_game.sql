@@ -0,0 +1,8 @@
+-- Running totals for the "Who said it?" quote game, per player
+CREATE TABLE IF NOT EXISTS quote_game_scores (
+    guild_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    points INTEGER NOT NULL DEFAULT 0,
+    guesses INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (guild_id, user_id)
+);
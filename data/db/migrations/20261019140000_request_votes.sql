@@ -0,0 +1,7 @@
+-- Votes for feature requests, one per user. The old running totals in requests.votes can't be
+-- tied to anyone, so counts come from this table instead.
+CREATE TABLE IF NOT EXISTS request_votes (
+    request_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    PRIMARY KEY (request_id, user_id)
+);
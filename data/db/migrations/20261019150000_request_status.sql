@@ -0,0 +1,20 @@
+-- Requests keep their history instead of being deleted when they're finished
+ALTER TABLE requests ADD COLUMN status TEXT NOT NULL DEFAULT 'open';
+ALTER TABLE requests ADD COLUMN status_reason TEXT;
+
+-- Every status a request has been in. Requests filed before this was tracked were opened at
+-- an unknown time.
+CREATE TABLE IF NOT EXISTS request_status_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    request_id INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    reason TEXT,
+    changed_by INTEGER,
+    changed_at REAL
+);
+
+CREATE INDEX IF NOT EXISTS request_status_changes_request
+    ON request_status_changes (request_id, id);
+
+INSERT INTO request_status_changes (request_id, status, changed_by)
+SELECT id, 'open', CAST(user AS INTEGER) FROM requests;
@@ -0,0 +1,4 @@
+-- Users who don't want to hear when requests they filed or voted for are closed
+CREATE TABLE IF NOT EXISTS request_notification_opt_outs (
+    user_id INTEGER PRIMARY KEY
+);
@@ -0,0 +1,2 @@
+-- Lines of conversation quotes as a JSON array of {speaker, line}, NULL for other quotes
+ALTER TABLE quote_audit ADD COLUMN old_lines TEXT;
@@ -0,0 +1,3 @@
+-- Totals from before votes were kept per user. They can't be tied to anyone or taken back,
+-- but still count towards each request's votes.
+ALTER TABLE requests RENAME COLUMN votes TO legacy_votes;
@@ -1,13 +1,18 @@
+use crate::constants::get_update_channel_id;
+use crate::data::bestof::{fetch_source_message, post_message_as_embed, Nomination};
 use crate::{Context, Error};
 use chrono::{DateTime, Utc};
-use log::{debug, error};
+use log::{debug, error, warn};
 use poise::serenity_prelude as serenity;
 
+// All embeds in a message share a 6000 character limit, so keep each of the top 10 short
+const TOP_EMBED_CONTENT_LENGTH: usize = 400;
+
 /// Messages with a certain number of reactions.
 #[poise::command(
     slash_command,
     track_edits,
-    subcommands("random", "search_history", "top")
+    subcommands("random", "search_history", "backfill_archive", "top", "stats")
 )]
 pub async fn bestof(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
@@ -19,17 +24,22 @@ pub async fn random(ctx: Context<'_>) -> Result<(), Error> {
     // Defer the response to give more time for the command to execute
     ctx.defer().await?;
 
-    let embed = ctx
+    let bestof = ctx
         .data()
         .bestof
         .lock()
         .await
-        .get_random_bestof_embed()
+        .get_random_bestof(ctx.serenity_context(), ctx.channel_id())
         .await?;
+    let Some(bestof) = bestof else {
+        ctx.reply("No bestofs available to post here.").await?;
+        return Ok(());
+    };
 
     ctx.send(poise::CreateReply {
         content: Some("*Here's a random bestof:*".to_string()),
-        embeds: vec![embed],
+        embeds: vec![bestof.create_embed(ctx.serenity_context()).await?],
+        attachments: bestof.archived_attachment().await.into_iter().collect(),
         reply: true,
         ..Default::default()
     })
@@ -72,6 +82,41 @@ pub async fn search_history(
     Ok(())
 }
 
+/// Archive the images of bestofs added before images were archived.
+#[poise::command(slash_command, track_edits, hide_in_help, owners_only)]
+pub async fn backfill_archive(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let messages = ctx.data().bestof.lock().await.get_messages();
+    let mut archived = 0;
+    for message in messages
+        .iter()
+        .filter(|msg| msg.image.is_some() && msg.archived_image.is_none())
+    {
+        // The stored attachment URL has expired, so archive from a fresh copy of the message
+        match fetch_source_message(ctx.serenity_context(), message).await {
+            Ok(Some(source)) => {
+                let name = ctx
+                    .data()
+                    .bestof
+                    .lock()
+                    .await
+                    .backfill_archived_image(&source)
+                    .await;
+                if name.is_some() {
+                    archived += 1;
+                }
+            }
+            Ok(None) => debug!("Bestof {:?} was deleted, nothing to archive", message.id),
+            Err(why) => warn!("Failed to fetch bestof {:?}: {:?}", message.id, why),
+        }
+    }
+
+    ctx.reply(format!("Archived the images of {} bestofs.", archived))
+        .await?;
+    Ok(())
+}
+
 /// Get the top 10 most reacted messages with an optional filter.
 #[poise::command(slash_command, track_edits)]
 pub async fn top(
@@ -94,12 +139,24 @@ pub async fn top(
         .bestof
         .lock()
         .await
-        .get_top_reacted_messages(ctx.serenity_context(), user, channel, time_filter)
+        .get_top_reacted_messages(
+            ctx.serenity_context(),
+            user,
+            channel,
+            time_filter,
+            ctx.channel_id(),
+        )
         .await?;
 
     let mut embeds = Vec::new();
+    let mut attachments = Vec::new();
     for message in top_messages {
-        embeds.push(message.create_embed()?);
+        embeds.push(
+            message
+                .create_embed_with_limit(ctx.serenity_context(), TOP_EMBED_CONTENT_LENGTH)
+                .await?,
+        );
+        attachments.extend(message.archived_attachment().await);
     }
 
     match embeds.len() {
@@ -110,6 +167,7 @@ pub async fn top(
             ctx.send(poise::CreateReply {
                 content: Some("*Top messages:*".to_string()),
                 embeds,
+                attachments,
                 reply: true,
                 ..Default::default()
             })
@@ -119,3 +177,112 @@ pub async fn top(
 
     Ok(())
 }
+
+/// Show bestof statistics for a user.
+#[poise::command(slash_command, track_edits)]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "User to show stats for, defaults to you"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    // Defer the response to give more time for the command to execute
+    ctx.defer().await?;
+
+    let user = user.unwrap_or_else(|| ctx.author().clone());
+
+    // Prefer the server nickname and avatar when we're in a guild
+    let (display_name, avatar_url) = match ctx.guild_id() {
+        Some(guild_id) => match guild_id.member(ctx, user.id).await {
+            Ok(member) => (member.display_name().to_string(), member.face()),
+            Err(_) => (user.name.clone(), user.face()),
+        },
+        None => (user.name.clone(), user.face()),
+    };
+
+    let stats = ctx.data().bestof.lock().await.get_user_stats(&user.name);
+
+    match stats {
+        None => {
+            ctx.reply(format!("{} doesn't have any bestofs yet :(", display_name))
+                .await?;
+        }
+        Some(stats) => {
+            ctx.send(poise::CreateReply {
+                embeds: vec![stats.create_embed(&display_name, avatar_url)],
+                reply: true,
+                ..Default::default()
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Nominate a message for bestof.
+#[poise::command(context_menu_command = "Nominate for bestof")]
+pub async fn nominate(
+    ctx: Context<'_>,
+    #[description = "Message to nominate"] msg: serenity::Message,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    if msg.author.bot {
+        ctx.say("Bot messages can't be nominated for bestof.")
+            .await?;
+        return Ok(());
+    }
+
+    // Owners nominating a message approve it straight away
+    let approve = ctx.framework().options().owners.contains(&ctx.author().id);
+
+    let nomination = {
+        let mut bestof = ctx.data().bestof.lock().await;
+        let db = ctx.data().db.lock().await;
+        bestof
+            .nominate(ctx.serenity_context(), &db, &msg, ctx.author().id, approve)
+            .await?
+    };
+
+    let response = match nomination {
+        Nomination::Counted {
+            nominations,
+            required,
+        } => format!(
+            "Nominated! This message has {} of {} nominations needed.",
+            nominations, required
+        ),
+        Nomination::AlreadyNominated {
+            nominations,
+            required,
+        } => format!(
+            "You've already nominated this message ({} of {} nominations).",
+            nominations, required
+        ),
+        Nomination::AlreadyBestOf => "This message is already a bestof!".to_string(),
+        Nomination::Approved(bestof) => {
+            let update_channel = serenity::ChannelId::new(get_update_channel_id());
+            let channel = ctx
+                .data()
+                .bestof
+                .lock()
+                .await
+                .route(ctx.serenity_context(), &bestof, update_channel)
+                .await;
+
+            // Messages from restricted channels are only reposted somewhere just as restricted
+            if let Some(channel) = channel {
+                post_message_as_embed(
+                    ctx.serenity_context(),
+                    &bestof,
+                    channel,
+                    Some(String::from("*Nominated and stored this bestof:*")),
+                )
+                .await?;
+            }
+            "This message is now a bestof!".to_string()
+        }
+    };
+
+    ctx.say(response).await?;
+    Ok(())
+}
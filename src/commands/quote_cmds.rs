@@ -1,36 +1,192 @@
-use crate::{Context, Error};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::data::card::render_quote_card;
+use crate::data::quote_files::{read_records, write_records, QuoteFileFormat, QuoteRecord};
+use crate::data::quotes::{
+    parse_conversation, AddQuote, ImportDuplicate, NewQuote, QuoteAuthor, QuoteMessage,
+};
+use crate::{ApplicationContext, Context, Error};
+use chrono::NaiveDate;
+use log::warn;
+use poise::serenity_prelude as serenity;
+
+const SEARCH_RESULTS_PER_PAGE: usize = 5;
+const TOP_QUOTES: i64 = 10;
+const LISTED_QUOTE_LENGTH: usize = 200;
+const HISTORY_LENGTH: i64 = 10;
+const STATS_LEADERBOARD: i64 = 5;
+const STATS_MONTHS: i64 = 12;
+const STATS_QUOTE_LENGTH: usize = 120;
+const GAME_DECOYS: i64 = 3;
+const GAME_ANSWER_SECONDS: u64 = 20;
+// Pause after revealing an answer before the next round starts
+const GAME_ROUND_BREAK_SECONDS: u64 = 5;
+const GAME_LEADERBOARD: i64 = 10;
+// Longest author name that fits on a button
+const MAX_BUTTON_LABEL_LENGTH: usize = 80;
+const MAX_IMPORT_BYTES: u32 = 10 * 1024 * 1024;
+// Longer import reports are attached as a file instead
+const MAX_REPORT_LENGTH: usize = 1900;
 
 /// Messages with a certain number of reactions.
-#[poise::command(slash_command, track_edits, subcommands("random", "store"))]
+#[poise::command(
+    slash_command,
+    track_edits,
+    subcommands(
+        "random",
+        "store",
+        "search",
+        "get",
+        "edit",
+        "delete",
+        "top",
+        "card",
+        "import",
+        "export",
+        "history",
+        "conversation",
+        "alias",
+        "stats",
+        "game",
+        "fake"
+    )
+)]
 pub async fn quote(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
 /// Post a random quote with an optional filter.
-#[poise::command(slash_command, track_edits)]
+#[poise::command(slash_command, track_edits, guild_only)]
 pub async fn random(
     ctx: Context<'_>,
     #[description = "Optional author to filter by"]
+    #[autocomplete = "autocomplete_author"]
     #[lazy]
     author: Option<String>,
+    #[description = "Optional user to filter by, under any of their names"] user: Option<
+        serenity::User,
+    >,
+    #[description = "Post the quote as an image"] card: Option<bool>,
 ) -> Result<(), Error> {
     // Defer the response to give more time for the command to execute
     ctx.defer().await?;
 
-    let embed = ctx
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    // A user is more specific than a name, so it wins if both are given
+    let (filter, author) = match (&user, author) {
+        (Some(user), _) => (
+            Some(QuoteAuthor::User(user.id)),
+            Some(
+                user.global_name
+                    .clone()
+                    .unwrap_or_else(|| user.name.clone()),
+            ),
+        ),
+        (None, Some(author)) => (Some(QuoteAuthor::Name(author.clone())), Some(author)),
+        (None, None) => (None, None),
+    };
+    let quote = ctx
         .data()
         .quotes
         .lock()
         .await
-        .get_random_quote(author.clone())
-        .await?
-        .create_embed();
+        .get_random_quote(guild_id, filter)
+        .await?;
+
+    let quote = match (quote, &author) {
+        (Some(quote), _) => quote,
+        (None, Some(author)) => {
+            ctx.reply(format!("No quotes by {} :(", author)).await?;
+            return Ok(());
+        }
+        (None, None) => {
+            ctx.reply("No quotes stored yet :(").await?;
+            return Ok(());
+        }
+    };
+
+    let votes = ctx.data().quotes.lock().await.get_votes(quote.id).await?;
 
     let message = match author {
         Some(author) => format!("*Here's a random quote by {}:*", author),
         None => "*Here's a random quote:*".to_string(),
     };
 
+    let mut reply = poise::CreateReply {
+        content: Some(message),
+        components: Some(vec![votes.create_buttons(quote.id)]),
+        reply: true,
+        ..Default::default()
+    };
+    if card.unwrap_or(false) {
+        let png = render_quote_card(ctx.serenity_context(), guild_id, &quote).await?;
+        reply = reply.attachment(serenity::CreateAttachment::bytes(
+            png,
+            format!("quote-{}.png", quote.id),
+        ));
+    } else {
+        reply = reply.embed(quote.create_embed(ctx.serenity_context()).await);
+    }
+
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Show the best rated quotes.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn top(
+    ctx: Context<'_>,
+    #[description = "Optional author to filter by"]
+    #[autocomplete = "autocomplete_author"]
+    author: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let top_quotes = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_top_quotes(guild_id, author.clone().map(QuoteAuthor::Name), TOP_QUOTES)
+        .await?;
+
+    if top_quotes.is_empty() {
+        ctx.reply("No rated quotes yet :(").await?;
+        return Ok(());
+    }
+
+    let lines = top_quotes
+        .iter()
+        .enumerate()
+        .map(|(rank, rated)| {
+            format!(
+                "{}. **#{}** ({:+}) {} — *{}*",
+                rank + 1,
+                rated.quote.id,
+                rated.score,
+                truncate_quote(&rated.quote.quote, LISTED_QUOTE_LENGTH),
+                rated.quote.author
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = serenity::CreateEmbed::default()
+        .color(serenity::Colour::GOLD)
+        .description(lines);
+
+    let message = match author {
+        Some(author) => format!("*Top quotes by {}:*", author),
+        None => "*Top quotes:*".to_string(),
+    };
+
     ctx.send(poise::CreateReply {
         content: Some(message),
         embeds: vec![embed],
@@ -42,25 +198,582 @@ pub async fn random(
     Ok(())
 }
 
-/// Store a quote.
-#[poise::command(slash_command, track_edits)]
-pub async fn store(
+/// Show the most recent daily quotes.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn history(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let posts = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_daily_history(guild_id, HISTORY_LENGTH)
+        .await?;
+
+    if posts.is_empty() {
+        ctx.reply("No daily quotes posted yet :(").await?;
+        return Ok(());
+    }
+
+    let lines = posts
+        .iter()
+        .map(|post| match (&post.quote, &post.author) {
+            (Some(quote), Some(author)) => format!(
+                "<t:{}:d> **#{}** {} — *{}*",
+                post.posted_at as i64,
+                post.quote_id,
+                truncate_quote(quote, LISTED_QUOTE_LENGTH),
+                author
+            ),
+            _ => format!(
+                "<t:{}:d> **#{}** *(deleted)*",
+                post.posted_at as i64, post.quote_id
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let embed = serenity::CreateEmbed::default()
+        .color(serenity::Colour::GOLD)
+        .description(lines);
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Recent daily quotes:*".to_string()),
+        embeds: vec![embed],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Show statistics about the stored quotes.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn stats(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let stats = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_stats(guild_id, STATS_LEADERBOARD, STATS_MONTHS)
+        .await?;
+
+    if stats.total == 0 {
+        ctx.reply("No quotes stored yet :(").await?;
+        return Ok(());
+    }
+
+    let mut embed = serenity::CreateEmbed::default()
+        .color(serenity::Colour::GOLD)
+        .title("Most quoted")
+        .description(format!("**{}** quotes stored", stats.total));
+
+    for (rank, person) in stats.people.iter().enumerate() {
+        let mut value = String::new();
+        if let Some(user_id) = person.user_id {
+            value.push_str(&format!("<@{}>\n", user_id));
+        }
+        value.push_str(&format!(
+            "Longest: **#{}** {}\nShortest: **#{}** {}",
+            person.longest_id,
+            truncate_quote(&person.longest, STATS_QUOTE_LENGTH),
+            person.shortest_id,
+            truncate_quote(&person.shortest, STATS_QUOTE_LENGTH)
+        ));
+        let quotes = match person.quotes {
+            1 => "1 quote".to_string(),
+            count => format!("{} quotes", count),
+        };
+        embed = embed.field(
+            format!("{}. {} — {}", rank + 1, person.name, quotes),
+            value,
+            false,
+        );
+    }
+
+    if !stats.submitters.is_empty() {
+        let submitters = stats
+            .submitters
+            .iter()
+            .enumerate()
+            .map(|(rank, (user_id, count))| format!("{}. <@{}> — {}", rank + 1, user_id, count))
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Top submitters", submitters, true);
+    }
+
+    if !stats.months.is_empty() {
+        let months = stats
+            .months
+            .iter()
+            .map(|(month, count)| {
+                let month = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                    .map(|date| date.format("%b %Y").to_string())
+                    .unwrap_or_else(|_| month.clone());
+                format!("{}: {}", month, count)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        embed = embed.field("Added per month", months, true);
+    }
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Quote stats:*".to_string()),
+        embeds: vec![embed],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Make up a quote from the stored ones.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn fake(
     ctx: Context<'_>,
-    #[description = "Quote to store"] quote: String,
-    #[description = "Author of the quote"] author: String,
+    #[description = "Optional author to imitate"]
+    #[autocomplete = "autocomplete_author"]
+    author: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let fake = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_fake_quote(guild_id, author.as_deref())
+        .await?;
+
+    let fake = match (fake, &author) {
+        (Some(fake), _) => fake,
+        (None, Some(author)) => {
+            ctx.reply(format!("No quotes by {} :(", author)).await?;
+            return Ok(());
+        }
+        (None, None) => {
+            ctx.reply("No quotes stored yet :(").await?;
+            return Ok(());
+        }
+    };
+
+    let title = match &author {
+        Some(author) => format!("Something {} never said", author.trim()),
+        None => "Something nobody ever said".to_string(),
+    };
+    let embed = serenity::CreateEmbed::default()
+        .color(serenity::Colour::DARK_GREY)
+        .title(title)
+        .description(fake)
+        .footer(serenity::CreateEmbedFooter::new(
+            "Fake quote, made up from the stored quotes",
+        ));
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Here's a fake quote:*".to_string()),
+        embeds: vec![embed],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Play "Who said it?" with the stored quotes.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("game_play", "game_leaderboard")
+)]
+pub async fn game(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Guess who said random quotes, over one or more rounds.
+#[poise::command(slash_command, guild_only, rename = "play")]
+pub async fn game_play(
+    ctx: Context<'_>,
+    #[description = "Number of quotes to guess, 1 by default"]
+    #[min = 1]
+    #[max = 10]
+    rounds: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let rounds = rounds.unwrap_or(1);
+    let ctx_id = ctx.id();
+
+    // Points and guesses for each player over the whole session
+    let mut standings: HashMap<serenity::UserId, (i64, i64)> = HashMap::new();
+
+    for number in 1..=rounds {
+        let round = ctx
+            .data()
+            .quotes
+            .lock()
+            .await
+            .get_game_round(guild_id, GAME_DECOYS)
+            .await?;
+        let Some(round) = round else {
+            ctx.reply("There need to be quotes by at least two people to play :(")
+                .await?;
+            return Ok(());
+        };
+
+        let button_prefix = format!("{}game{}:", ctx_id, number);
+        let embed = serenity::CreateEmbed::default()
+            .color(serenity::Colour::GOLD)
+            .title("Who said it?")
+            .description(&round.quote.quote)
+            .footer(serenity::CreateEmbedFooter::new(format!(
+                "Round {} of {}",
+                number, rounds
+            )));
+        let reply = ctx
+            .send(poise::CreateReply {
+                content: Some(format!(
+                    "*Who said it? You have {} seconds to guess:*",
+                    GAME_ANSWER_SECONDS
+                )),
+                embeds: vec![embed],
+                components: Some(game_buttons(&button_prefix, &round.choices, None)),
+                reply: true,
+                ..Default::default()
+            })
+            .await?;
+
+        // Everyone gets one guess until the time runs out
+        let mut guesses: Vec<(serenity::UserId, bool)> = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(GAME_ANSWER_SECONDS);
+        while let Some(press) = serenity::ComponentInteractionCollector::new(ctx)
+            .filter({
+                let button_prefix = button_prefix.clone();
+                move |press| press.data.custom_id.starts_with(&button_prefix)
+            })
+            .timeout(deadline.saturating_duration_since(Instant::now()))
+            .await
+        {
+            let Some(choice) = press
+                .data
+                .custom_id
+                .strip_prefix(&button_prefix)
+                .and_then(|choice| choice.parse::<usize>().ok())
+                .filter(|choice| *choice < round.choices.len())
+            else {
+                continue;
+            };
+
+            let response = match guesses.iter().any(|(user, _)| *user == press.user.id) {
+                true => "You've already guessed this round.".to_string(),
+                false => {
+                    guesses.push((press.user.id, choice == round.answer));
+                    format!("You guessed **{}**.", round.choices[choice])
+                }
+            };
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    serenity::CreateInteractionResponse::Message(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .content(response)
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+        }
+
+        ctx.data()
+            .quotes
+            .lock()
+            .await
+            .record_game_guesses(guild_id, &guesses)
+            .await?;
+        for (user, correct) in &guesses {
+            let (points, guessed) = standings.entry(*user).or_default();
+            *points += *correct as i64;
+            *guessed += 1;
+        }
+
+        let winners = guesses
+            .iter()
+            .filter(|(_, correct)| *correct)
+            .map(|(user, _)| format!("<@{}>", user))
+            .collect::<Vec<_>>();
+        let summary = match winners.is_empty() {
+            true => "Nobody :(".to_string(),
+            false => winners.join(", "),
+        };
+        let revealed = round
+            .quote
+            .create_embed(ctx.serenity_context())
+            .await
+            .field("Guessed right", summary, false);
+        reply
+            .edit(
+                ctx,
+                poise::CreateReply::default()
+                    .content(format!(
+                        "*Round {} of {}: it was {}!*",
+                        number, rounds, round.quote.author
+                    ))
+                    .embed(revealed)
+                    .components(game_buttons(
+                        &button_prefix,
+                        &round.choices,
+                        Some(round.answer),
+                    )),
+            )
+            .await?;
+
+        if number < rounds {
+            tokio::time::sleep(Duration::from_secs(GAME_ROUND_BREAK_SECONDS)).await;
+        }
+    }
+
+    if rounds > 1 && !standings.is_empty() {
+        let mut standings: Vec<_> = standings.into_iter().collect();
+        standings.sort_by(|(_, a), (_, b)| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        let lines = standings
+            .iter()
+            .enumerate()
+            .map(|(rank, (user, (points, guessed)))| {
+                format!("{}. <@{}> — {} of {}", rank + 1, user, points, guessed)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        ctx.send(poise::CreateReply {
+            content: Some("*Final scores:*".to_string()),
+            embeds: vec![serenity::CreateEmbed::default()
+                .color(serenity::Colour::GOLD)
+                .description(lines)],
+            ..Default::default()
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Buttons for each author in a round of the guessing game, disabled with the right answer
+/// highlighted once it's revealed.
+fn game_buttons(
+    prefix: &str,
+    choices: &[String],
+    answer: Option<usize>,
+) -> Vec<serenity::CreateActionRow> {
+    let buttons = choices
+        .iter()
+        .enumerate()
+        .map(|(i, choice)| {
+            let style = match answer {
+                Some(answer) if answer == i => serenity::ButtonStyle::Success,
+                _ => serenity::ButtonStyle::Secondary,
+            };
+            serenity::CreateButton::new(format!("{}{}", prefix, i))
+                .label(
+                    choice
+                        .chars()
+                        .take(MAX_BUTTON_LABEL_LENGTH)
+                        .collect::<String>(),
+                )
+                .style(style)
+                .disabled(answer.is_some())
+        })
+        .collect();
+
+    vec![serenity::CreateActionRow::Buttons(buttons)]
+}
+
+/// Show the players with the most points in the guessing game.
+#[poise::command(slash_command, guild_only, rename = "leaderboard")]
+pub async fn game_leaderboard(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let scores = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_game_leaderboard(guild_id, GAME_LEADERBOARD)
+        .await?;
+
+    if scores.is_empty() {
+        ctx.reply("Nobody has scored yet :(").await?;
+        return Ok(());
+    }
+
+    let lines = scores
+        .iter()
+        .enumerate()
+        .map(|(rank, score)| {
+            format!(
+                "{}. <@{}> — **{}** points ({} guesses)",
+                rank + 1,
+                score.user_id,
+                score.points,
+                score.guesses
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Who said it? leaderboard:*".to_string()),
+        embeds: vec![serenity::CreateEmbed::default()
+            .color(serenity::Colour::GOLD)
+            .description(lines)],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Shorten a quote for listing alongside others.
+fn truncate_quote(quote: &str, length: usize) -> String {
+    let mut text = quote.chars().take(length).collect::<String>();
+    if text.len() < quote.len() {
+        text.push('…');
+    }
+    text
+}
+
+/// Search the stored quotes.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn search(
+    ctx: Context<'_>,
+    #[description = "Words to search for"] text: String,
+    #[description = "Optional author to filter by"]
+    #[autocomplete = "autocomplete_author"]
+    author: Option<String>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
-    let stored = ctx
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let results = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .search_quotes(guild_id, &text, author.map(QuoteAuthor::Name))
+        .await?;
+
+    if results.is_empty() {
+        ctx.reply(format!("No quotes found for \"{}\" :(", text))
+            .await?;
+        return Ok(());
+    }
+
+    let page_count = results.len().div_ceil(SEARCH_RESULTS_PER_PAGE);
+    let pages = results
+        .chunks(SEARCH_RESULTS_PER_PAGE)
+        .enumerate()
+        .map(|(page, chunk)| {
+            let lines = chunk
+                .iter()
+                .map(|result| {
+                    format!(
+                        "**#{}** {} — *{}*",
+                        result.id, result.snippet, result.author
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!(
+                "*Quotes matching \"{}\" (page {} of {}):*\n{}",
+                text,
+                page + 1,
+                page_count,
+                lines
+            )
+        })
+        .collect::<Vec<_>>();
+    let pages = pages.iter().map(String::as_str).collect::<Vec<_>>();
+
+    poise::builtins::paginate(ctx, &pages).await?;
+
+    Ok(())
+}
+
+/// Post a specific quote.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn get(
+    ctx: Context<'_>,
+    #[description = "ID of the quote"] id: i32,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let quote = ctx
         .data()
         .quotes
         .lock()
         .await
-        .add_quote(quote, author)
+        .get_quote(guild_id, id)
         .await?;
 
+    match quote {
+        Some(quote) => {
+            ctx.send(poise::CreateReply {
+                embeds: vec![quote.create_embed(ctx.serenity_context()).await],
+                reply: true,
+                ..Default::default()
+            })
+            .await?;
+        }
+        None => {
+            ctx.reply(format!("No quote #{} :(", id)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Post a quote as an image.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn card(
+    ctx: Context<'_>,
+    #[description = "ID of the quote"] id: i32,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let Some(quote) = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_quote(guild_id, id)
+        .await?
+    else {
+        ctx.reply(format!("No quote #{} :(", id)).await?;
+        return Ok(());
+    };
+
+    let png = render_quote_card(ctx.serenity_context(), guild_id, &quote).await?;
     ctx.send(poise::CreateReply {
-        content: Some("*Stored the following*: ".to_string()),
-        embeds: vec![stored.create_embed()],
+        attachments: vec![serenity::CreateAttachment::bytes(
+            png,
+            format!("quote-{}.png", quote.id),
+        )],
         reply: true,
         ..Default::default()
     })
@@ -68,3 +781,651 @@ pub async fn store(
 
     Ok(())
 }
+
+/// Fix the text or author of a quote you added.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn edit(
+    ctx: Context<'_>,
+    #[description = "ID of the quote"] id: i32,
+    #[description = "New text for the quote"] quote: Option<String>,
+    #[description = "New author of the quote"]
+    #[autocomplete = "autocomplete_author"]
+    author: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    if quote.is_none() && author.is_none() {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("Give a new quote or author to change.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Hold the lock so the quote can't change between checking and editing it
+    let mut quotes = ctx.data().quotes.lock().await;
+    let Some(existing) = quotes.get_quote(guild_id, id).await? else {
+        ctx.reply(format!("No quote #{} :(", id)).await?;
+        return Ok(());
+    };
+    if !can_modify(ctx, &existing) {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("Only whoever added a quote can edit it.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+    if quote.is_some() && !existing.lines.is_empty() {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("The text of a conversation can't be edited, delete it and add it again instead.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let updated = quotes
+        .edit_quote(guild_id, id, quote, author, ctx.author().id)
+        .await?
+        .ok_or("Quote disappeared while editing")?;
+    drop(quotes);
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Updated the following*: ".to_string()),
+        embeds: vec![updated.create_embed(ctx.serenity_context()).await],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Delete a quote you added.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "ID of the quote"] id: i32,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    let mut quotes = ctx.data().quotes.lock().await;
+    let Some(existing) = quotes.get_quote(guild_id, id).await? else {
+        ctx.reply(format!("No quote #{} :(", id)).await?;
+        return Ok(());
+    };
+    if !can_modify(ctx, &existing) {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("Only whoever added a quote can delete it.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    quotes.delete_quote(guild_id, id, ctx.author().id).await?;
+    drop(quotes);
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Deleted the following*: ".to_string()),
+        embeds: vec![existing.create_embed(ctx.serenity_context()).await],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Whether the invoking user may edit or delete the quote: its submitter or a bot owner.
+fn can_modify(ctx: Context<'_>, quote: &QuoteMessage) -> bool {
+    let user_id = ctx.author().id;
+    quote.submitted_by == Some(user_id.get() as i64)
+        || ctx.framework().options().owners.contains(&user_id)
+}
+
+/// Import quotes from a CSV or JSON file.
+#[poise::command(slash_command, guild_only, owners_only, hide_in_help)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "CSV or JSON file with quote and author columns"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let Some(format) = QuoteFileFormat::from_filename(&file.filename) else {
+        ctx.reply("Only .csv and .json files can be imported.")
+            .await?;
+        return Ok(());
+    };
+    if file.size > MAX_IMPORT_BYTES {
+        ctx.reply("That file is too big to import.").await?;
+        return Ok(());
+    }
+
+    let contents = file.download().await?;
+    let rows = match read_records(format, &contents) {
+        Ok(rows) => rows,
+        Err(why) => {
+            ctx.reply(format!("Couldn't read {}: {}", file.filename, why))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let imported_by = ctx.author().id.get() as i64;
+    let rows = rows
+        .into_iter()
+        .map(|row| row.and_then(|record| record.into_new_quote(guild_id.get() as i64, imported_by)))
+        .collect();
+    let report = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .import_quotes(guild_id, rows)
+        .await?;
+
+    // Number CSV rows the way a spreadsheet would, counting the header
+    let row_label = |row: usize| match format {
+        QuoteFileFormat::Csv => format!("Row {}", row + 1),
+        QuoteFileFormat::Json => format!("Entry {}", row),
+    };
+
+    let mut lines = Vec::new();
+    if report.errors.is_empty() {
+        lines.push(format!("*Imported {} quotes.*", report.imported));
+    } else {
+        lines.push(format!(
+            "*Nothing was imported, {} rows need fixing:*",
+            report.errors.len()
+        ));
+        for (row, why) in &report.errors {
+            lines.push(format!("{}: {}", row_label(*row), why));
+        }
+    }
+    if !report.duplicates.is_empty() {
+        lines.push(format!("*Skipped {} duplicates:*", report.duplicates.len()));
+        for (row, duplicate) in &report.duplicates {
+            lines.push(match duplicate {
+                ImportDuplicate::Stored(id) => {
+                    format!("{} is already stored as quote #{}", row_label(*row), id)
+                }
+                ImportDuplicate::Row(earlier) => {
+                    format!(
+                        "{} repeats {}",
+                        row_label(*row),
+                        row_label(*earlier).to_lowercase()
+                    )
+                }
+            });
+        }
+    }
+
+    let report = lines.join("\n");
+    if report.len() <= MAX_REPORT_LENGTH {
+        ctx.reply(report).await?;
+    } else {
+        ctx.send(poise::CreateReply {
+            content: Some(format!("{} The full report is attached.", lines[0])),
+            attachments: vec![serenity::CreateAttachment::bytes(
+                report.into_bytes(),
+                "import-report.txt",
+            )],
+            reply: true,
+            ..Default::default()
+        })
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Export this server's quotes as a CSV or JSON file.
+#[poise::command(slash_command, guild_only)]
+pub async fn export(
+    ctx: Context<'_>,
+    #[description = "File format"] format: QuoteFileFormat,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    let quotes = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_all_quotes(guild_id)
+        .await?;
+
+    let records = quotes
+        .iter()
+        .map(QuoteRecord::from_quote)
+        .collect::<Vec<_>>();
+    let contents = write_records(format, &records)?;
+
+    ctx.send(poise::CreateReply {
+        content: Some(format!("*Exported {} quotes:*", records.len())),
+        attachments: vec![serenity::CreateAttachment::bytes(
+            contents,
+            format!("quotes.{}", format.extension()),
+        )],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Link the names quotes are stored under to Discord users.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("alias_add", "alias_remove", "alias_list")
+)]
+pub async fn alias(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Link a name to a Discord user.
+#[poise::command(slash_command, guild_only, rename = "add")]
+pub async fn alias_add(
+    ctx: Context<'_>,
+    #[description = "Name quotes are stored under"]
+    #[autocomplete = "autocomplete_author"]
+    name: String,
+    #[description = "User the name belongs to"] user: serenity::User,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    ctx.data()
+        .quotes
+        .lock()
+        .await
+        .add_alias(guild_id, &name, user.id, ctx.author().id)
+        .await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Quotes by {} are now linked to <@{}>.",
+                name.trim(),
+                user.id
+            ))
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+            .reply(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Unlink a name from a Discord user.
+#[poise::command(slash_command, guild_only, rename = "remove")]
+pub async fn alias_remove(
+    ctx: Context<'_>,
+    #[description = "Name to unlink"]
+    #[autocomplete = "autocomplete_author"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    let removed = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .remove_alias(guild_id, &name)
+        .await?;
+
+    let message = match removed {
+        Some(alias) => format!(
+            "{} is no longer linked to <@{}>.",
+            alias.alias, alias.user_id
+        ),
+        None => format!("{} isn't linked to anyone.", name.trim()),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(message)
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+            .reply(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// List the names linked to Discord users.
+#[poise::command(slash_command, guild_only, rename = "list")]
+pub async fn alias_list(
+    ctx: Context<'_>,
+    #[description = "Optional user to list the names of"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    let aliases = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_aliases(guild_id, user.as_ref().map(|user| user.id))
+        .await?;
+
+    if aliases.is_empty() {
+        ctx.reply("No names are linked yet :(").await?;
+        return Ok(());
+    }
+
+    // One line per user with all of their names
+    let mut lines: Vec<(i64, Vec<String>)> = Vec::new();
+    for alias in aliases {
+        match lines.last_mut() {
+            Some((user_id, names)) if *user_id == alias.user_id => names.push(alias.alias),
+            _ => lines.push((alias.user_id, vec![alias.alias])),
+        }
+    }
+    let description = lines
+        .iter()
+        .map(|(user_id, names)| format!("<@{}>: {}", user_id, names.join(", ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ctx.send(poise::CreateReply {
+        content: Some("*Linked names:*".to_string()),
+        embeds: vec![serenity::CreateEmbed::default()
+            .color(serenity::Colour::GOLD)
+            .description(description)],
+        reply: true,
+        ..Default::default()
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Suggest authors from the stored quotes.
+async fn autocomplete_author(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+
+    match ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .get_authors(guild_id, partial)
+        .await
+    {
+        Ok(authors) => authors,
+        Err(why) => {
+            warn!("Failed to autocomplete quote authors: {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+/// Store a quote.
+#[poise::command(slash_command, track_edits, guild_only)]
+pub async fn store(
+    ctx: Context<'_>,
+    #[description = "Quote to store"] quote: String,
+    #[description = "Author of the quote"] author: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+    store_quote(
+        ctx,
+        NewQuote {
+            quote,
+            author,
+            submitted_by: ctx.author().id.get() as i64,
+            guild_id: guild_id.get() as i64,
+            ..Default::default()
+        },
+    )
+    .await
+}
+
+/// Store a quote and show it. If it looks like a quote that's already stored, let the user
+/// choose between storing it anyway and seeing the existing one instead.
+async fn store_quote(ctx: Context<'_>, quote: NewQuote) -> Result<(), Error> {
+    let added = ctx
+        .data()
+        .quotes
+        .lock()
+        .await
+        .add_quote(quote.clone())
+        .await?;
+    let existing = match added {
+        AddQuote::Added(stored) => {
+            ctx.send(poise::CreateReply {
+                content: Some("*Stored the following*: ".to_string()),
+                embeds: vec![stored.create_embed(ctx.serenity_context()).await],
+                reply: true,
+                ..Default::default()
+            })
+            .await?;
+            return Ok(());
+        }
+        AddQuote::Duplicate(existing) => existing,
+    };
+
+    let ctx_id = ctx.id();
+    let store_button_id = format!("{}store", ctx_id);
+    let show_button_id = format!("{}show", ctx_id);
+    let buttons = |show_existing: bool| {
+        let mut buttons = vec![serenity::CreateButton::new(&store_button_id)
+            .label("Store anyway")
+            .style(serenity::ButtonStyle::Primary)];
+        if show_existing {
+            buttons.push(
+                serenity::CreateButton::new(&show_button_id)
+                    .label("Show existing")
+                    .style(serenity::ButtonStyle::Secondary),
+            );
+        }
+        vec![serenity::CreateActionRow::Buttons(buttons)]
+    };
+
+    let reply = ctx
+        .send(poise::CreateReply {
+            content: Some(format!(
+                "*That looks like quote #{}, which is already stored.*",
+                existing.id
+            )),
+            components: Some(buttons(true)),
+            reply: true,
+            ..Default::default()
+        })
+        .await?;
+
+    while let Some(press) = serenity::ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(std::time::Duration::from_secs(120))
+        .await
+    {
+        if press.data.custom_id == store_button_id {
+            let stored = ctx
+                .data()
+                .quotes
+                .lock()
+                .await
+                .add_quote_unchecked(quote)
+                .await?;
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    serenity::CreateInteractionResponse::UpdateMessage(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .content("*Stored the following*: ")
+                            .embed(stored.create_embed(ctx.serenity_context()).await)
+                            .components(Vec::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        } else if press.data.custom_id == show_button_id {
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    serenity::CreateInteractionResponse::UpdateMessage(
+                        serenity::CreateInteractionResponseMessage::new()
+                            .embed(existing.create_embed(ctx.serenity_context()).await)
+                            .components(buttons(false)),
+                    ),
+                )
+                .await?;
+        }
+    }
+
+    // Nobody chose in time, so take the buttons away
+    reply
+        .edit(ctx, poise::CreateReply::default().components(Vec::new()))
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Store a conversation"]
+struct ConversationModal {
+    #[name = "Conversation, one line each as Speaker: text"]
+    #[placeholder = "Alice: did you hear that?\nBob: hear what?"]
+    #[paragraph]
+    #[max_length = 4000]
+    conversation: String,
+}
+
+/// Store a conversation between several people.
+#[poise::command(slash_command, guild_only)]
+pub async fn conversation(ctx: ApplicationContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    let Some(modal) = poise::execute_modal::<_, _, ConversationModal>(
+        ctx,
+        None,
+        Some(std::time::Duration::from_secs(600)),
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let Some(lines) = parse_conversation(&modal.conversation) else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("A conversation needs at least two lines, each starting with `Speaker:`.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    store_quote(
+        ctx.into(),
+        NewQuote::conversation(lines, ctx.author().id.get() as i64, guild_id.get() as i64),
+    )
+    .await
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Save as quote"]
+struct SaveQuoteModal {
+    #[name = "Quote"]
+    #[paragraph]
+    #[max_length = 4000]
+    quote: String,
+    #[name = "Author"]
+    #[max_length = 100]
+    author: String,
+}
+
+/// Save a message as a quote.
+#[poise::command(context_menu_command = "Save as quote", guild_only)]
+pub async fn save_quote(
+    ctx: ApplicationContext<'_>,
+    #[description = "Message to save"] msg: serenity::Message,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("Quotes are only available in servers")?;
+
+    if msg.content.trim().is_empty() {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("That message doesn't have any text to quote.")
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let author = match msg.author_nick(ctx.serenity_context()).await {
+        Some(nick) => nick,
+        None => msg
+            .author
+            .global_name
+            .clone()
+            .unwrap_or_else(|| msg.author.name.clone()),
+    };
+
+    // Let the user trim the quote before it's stored
+    let defaults = SaveQuoteModal {
+        quote: msg.content.chars().take(4000).collect(),
+        author,
+    };
+    let Some(edited) = poise::execute_modal(
+        ctx,
+        Some(defaults),
+        Some(std::time::Duration::from_secs(300)),
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    store_quote(
+        ctx.into(),
+        NewQuote {
+            quote: edited.quote,
+            author: edited.author,
+            author_user_id: Some(msg.author.id.get() as i64),
+            timestamp: Some(msg.timestamp.unix_timestamp() as f64),
+            source_link: Some(msg.link()),
+            submitted_by: ctx.author().id.get() as i64,
+            created_at: None,
+            guild_id: guild_id.get() as i64,
+            lines: Vec::new(),
+        },
+    )
+    .await
+}
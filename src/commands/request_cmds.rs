@@ -1,8 +1,19 @@
+use crate::constants::get_request_notification_channel_id;
+use crate::data::requests::{FeatureRequest, RequestStatus};
 use crate::{Context, Error};
+use log::warn;
 use poise::{serenity_prelude as serenity, CreateReply};
 
+const LISTED_REQUESTS: i64 = 25;
+// Discord rejects messages over 2000 characters
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
 /// Bot feature request by voting.
-#[poise::command(slash_command, track_edits, subcommands("add", "vote", "complete"))]
+#[poise::command(
+    slash_command,
+    track_edits,
+    subcommands("add", "vote", "list", "show", "status", "complete", "notifications")
+)]
 pub async fn request(_ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
@@ -11,7 +22,9 @@ pub async fn request(_ctx: Context<'_>) -> Result<(), Error> {
 #[poise::command(slash_command, track_edits)]
 pub async fn add(
     ctx: Context<'_>,
-    #[description = "Feature request"] request: String,
+    #[description = "Feature request"]
+    #[max_length = 1000]
+    request: String,
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
@@ -34,95 +47,282 @@ pub async fn add(
     Ok(())
 }
 
-/// Present a voting menu for all of the active requests
+/// Post a voting menu for the active requests that anyone can use
 #[poise::command(slash_command, track_edits)]
 pub async fn vote(ctx: Context<'_>) -> Result<(), Error> {
     ctx.defer().await?;
 
-    let requests = ctx.data().requests.lock().await.get_requests().await?;
-
-    let reply = {
-        let components = requests
-            .iter()
-            .map(|request| {
-                serenity::CreateActionRow::Buttons(vec![serenity::CreateButton::new(format!(
-                    "{}",
-                    request.id
-                ))
-                .style(serenity::ButtonStyle::Primary)
-                .label(request.request.to_string())])
-            })
-            .collect::<Vec<_>>();
+    // Votes on the menu are handled in events::requestvote, so it keeps working after this
+    // command is done
+    let (embed, components) = ctx
+        .data()
+        .requests
+        .lock()
+        .await
+        .get_request_page(0)
+        .await?
+        .create_menu();
 
+    ctx.send(
         CreateReply::default()
             .content("Vote for your desired requests!")
-            .components(components)
+            .embed(embed)
+            .components(components),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Mark a request as completed
+#[poise::command(slash_command, track_edits, hide_in_help, owners_only)]
+pub async fn complete(
+    ctx: Context<'_>,
+    #[description = "Request id"] id: i32,
+    #[description = "Optional note on what was done"]
+    #[max_length = 500]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    change_status(ctx, id, RequestStatus::Done, reason).await
+}
+
+/// Move a request to a new status
+#[poise::command(slash_command, track_edits, hide_in_help, owners_only)]
+pub async fn status(
+    ctx: Context<'_>,
+    #[description = "Request id"] id: i32,
+    #[description = "New status"] state: RequestStatus,
+    #[description = "Optional reason for the change"]
+    #[max_length = 500]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    change_status(ctx, id, state, reason).await
+}
+
+async fn change_status(
+    ctx: Context<'_>,
+    id: i32,
+    status: RequestStatus,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let reason = reason.filter(|reason| !reason.trim().is_empty());
+    let updated = ctx
+        .data()
+        .requests
+        .lock()
+        .await
+        .set_request_status(id, status, reason, ctx.author().id)
+        .await?;
+
+    let Some((request, previous)) = updated else {
+        ctx.reply(format!("No request with id {}", id)).await?;
+        return Ok(());
     };
 
-    ctx.send(reply).await?;
+    ctx.reply(format!("Marked request {} as {}", request, status))
+        .await?;
+
+    // Everyone already heard about it the first time it was closed like this
+    if status.is_closed() && status != previous {
+        notify_closed(ctx, &request).await?;
+    }
+    Ok(())
+}
 
-    if let Some(mci) = serenity::ComponentInteractionCollector::new(ctx)
-        .author_id(ctx.author().id)
-        .channel_id(ctx.channel_id())
-        .timeout(std::time::Duration::from_secs(120))
+/// Tell whoever filed a closed request and everyone who voted for it, by DM where possible
+/// and with a mention in the notification channel otherwise.
+async fn notify_closed(ctx: Context<'_>, request: &FeatureRequest) -> Result<(), Error> {
+    let recipients = ctx
+        .data()
+        .requests
+        .lock()
         .await
-    {
-        let req_id = mci.data.clone().custom_id;
-        let req = ctx
-            .data()
-            .requests
-            .lock()
-            .await
-            .vote_request(req_id.parse::<i32>().unwrap())
+        .get_notification_recipients(request.id)
+        .await?;
+
+    let title = format!("{}. **{}**", request.id, request.request);
+    let mut change = format!(
+        "was marked **{}** by <@{}>.",
+        request.status,
+        ctx.author().id
+    );
+    if let Some(reason) = &request.status_reason {
+        change.push_str(&format!("\n> {}", reason));
+    }
+    change.push_str("\n*Use `/request notifications` to stop hearing about requests.*");
+
+    let mut undelivered = Vec::new();
+    for user_id in recipients {
+        let content = match user_id.to_string() == request.user {
+            true => format!("Your request {} {}", title, change),
+            false => format!("Request {}, which you voted for, {}", title, change),
+        };
+        let dm = serenity::CreateMessage::new().content(content);
+        let sent = match user_id.create_dm_channel(ctx).await {
+            Ok(channel) => channel.send_message(ctx, dm).await.map(|_| ()),
+            Err(why) => Err(why),
+        };
+        if let Err(why) = sent {
+            warn!(
+                "Failed to DM {} about request {}: {:?}",
+                user_id, request.id, why
+            );
+            undelivered.push(user_id);
+        }
+    }
+
+    if undelivered.is_empty() {
+        return Ok(());
+    }
+    let Some(channel_id) = get_request_notification_channel_id() else {
+        warn!(
+            "No notification channel to tell {} people about request {}",
+            undelivered.len(),
+            request.id
+        );
+        return Ok(());
+    };
+
+    // Announce the change, then mention everyone in as few messages as fit
+    let mut messages = vec![format!("Request {} {}", title, change)];
+    let mut mentions = String::new();
+    for user_id in undelivered {
+        let mention = format!("<@{}>", user_id);
+        if !mentions.is_empty() && mentions.len() + 1 + mention.len() > MAX_MESSAGE_LENGTH {
+            messages.push(std::mem::take(&mut mentions));
+        }
+        if !mentions.is_empty() {
+            mentions.push(' ');
+        }
+        mentions.push_str(&mention);
+    }
+    messages.push(mentions);
+
+    let channel_id = serenity::ChannelId::new(channel_id);
+    for content in messages {
+        channel_id
+            .send_message(ctx, serenity::CreateMessage::new().content(content))
             .await?;
+    }
 
-        let reqs = ctx.data().requests.lock().await.get_requests().await?;
-        let request_list = reqs
-            .iter()
-            .map(|request| request.to_string())
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let mut msg = mci.message.clone();
-        msg.edit(
-            ctx,
-            serenity::EditMessage::new()
-                .content(format!("Voted for {}!\n\n{}", req.request, request_list))
-                .components(vec![]), // Remove all buttons
-        )
+    Ok(())
+}
+
+/// Turn notifications about your requests and votes on or off
+#[poise::command(slash_command, track_edits)]
+pub async fn notifications(
+    ctx: Context<'_>,
+    #[description = "Whether to hear when requests you filed or voted for are closed"]
+    enabled: bool,
+) -> Result<(), Error> {
+    ctx.data()
+        .requests
+        .lock()
+        .await
+        .set_notifications(ctx.author().id, enabled)
         .await?;
 
-        mci.create_response(
-            ctx,
-            serenity::CreateInteractionResponse::UpdateMessage(
-                serenity::CreateInteractionResponseMessage::new()
-                    .content(format!("Voted for {}!\n\n{}", req.request, request_list))
-                    .components(vec![]), // Remove all buttons
-            ),
-        )
+    let message = match enabled {
+        true => "You'll hear when requests you filed or voted for are closed.",
+        false => "You won't hear about requests anymore.",
+    };
+    ctx.send(CreateReply::default().content(message).ephemeral(true))
         .await?;
-    }
 
     Ok(())
 }
 
-/// Mark a request as completed
-#[poise::command(slash_command, track_edits, hide_in_help, owners_only)]
-pub async fn complete(
+/// List requests, the active ones by default
+#[poise::command(slash_command, track_edits)]
+pub async fn list(
     ctx: Context<'_>,
-    #[description = "Request id"] id: String,
+    #[description = "Only list requests with this status"] status: Option<RequestStatus>,
 ) -> Result<(), Error> {
     ctx.defer().await?;
 
-    let deleted = ctx
+    let requests = ctx
         .data()
         .requests
         .lock()
         .await
-        .complete_request(id.parse::<i32>().unwrap())
+        .get_requests(status, LISTED_REQUESTS)
         .await?;
 
-    ctx.reply(format!("Marked request {} as completed", deleted))
+    let heading = match status {
+        Some(status) => format!("{} requests", status),
+        None => "Active requests".to_string(),
+    };
+    let description = match requests.is_empty() {
+        true => "None :(".to_string(),
+        false => requests
+            .iter()
+            .map(FeatureRequest::summary)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+
+    ctx.send(
+        CreateReply::default()
+            .embed(
+                serenity::CreateEmbed::default()
+                    .title(heading)
+                    .description(description),
+            )
+            .reply(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Show a request along with its history
+#[poise::command(slash_command, track_edits)]
+pub async fn show(ctx: Context<'_>, #[description = "Request id"] id: i32) -> Result<(), Error> {
+    ctx.defer().await?;
+
+    let request = ctx.data().requests.lock().await.get_request(id).await?;
+    let Some(request) = request else {
+        ctx.reply(format!("No request with id {}", id)).await?;
+        return Ok(());
+    };
+    let history = ctx
+        .data()
+        .requests
+        .lock()
+        .await
+        .get_request_history(id)
         .await?;
+
+    let timeline = history
+        .iter()
+        .map(|change| {
+            let mut line = match change.changed_at {
+                Some(changed_at) => format!("<t:{}:d> **{}**", changed_at as i64, change.status),
+                None => format!("**{}**", change.status),
+            };
+            if let Some(changed_by) = change.changed_by {
+                line.push_str(&format!(" by <@{}>", changed_by));
+            }
+            if let Some(reason) = &change.reason {
+                line.push_str(&format!(": {}", reason));
+            }
+            line
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let embed = serenity::CreateEmbed::default()
+        .title(format!("Request {}", request.id))
+        .description(&request.request)
+        .field("Requested by", format!("<@{}>", request.user), true)
+        .field("Votes", request.votes.to_string(), true)
+        .field("Status", request.status.to_string(), true)
+        .field("Timeline", timeline, false);
+
+    ctx.send(CreateReply::default().embed(embed).reply(true))
+        .await?;
+
     Ok(())
 }
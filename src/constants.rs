@@ -1,6 +1,8 @@
 use log::info;
 use std::env::var;
+use std::str::FromStr;
 
+pub const THICC_GUILD_ID: u64 = 561602796286378029;
 pub const QUOTES_CHANNEL_ID: u64 = 630235116475514891; // #quotes
 pub const DEV_DM_CHANNEL_ID: u64 = 563105728341082148; // #dm to sean
 
@@ -17,3 +19,73 @@ pub fn get_update_channel_id() -> u64 {
         }
     }
 }
+
+/// Read an environment variable, falling back to a default when it's unset or invalid.
+fn var_or<T: FromStr>(name: &str, default: T) -> T {
+    var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Number of reactions a message has to gain within the trending window to be called out.
+pub fn get_trending_min_reactions() -> i64 {
+    var_or("TRENDING_MIN_REACTIONS", 4)
+}
+
+/// Length of the window reaction velocity is measured over, in minutes.
+pub fn get_trending_window_minutes() -> i64 {
+    var_or("TRENDING_WINDOW_MINUTES", 10)
+}
+
+/// How long after being posted a message is still eligible to trend, in hours.
+pub fn get_trending_max_age_hours() -> i64 {
+    var_or("TRENDING_MAX_AGE_HOURS", 24)
+}
+
+/// Number of distinct users that have to nominate a message before it becomes a bestof.
+pub fn get_bestof_nominations_required() -> i64 {
+    var_or("BESTOF_NOMINATIONS_REQUIRED", 3)
+}
+
+/// Largest single attachment that gets archived locally, in bytes.
+pub fn get_archive_max_file_bytes() -> u64 {
+    var_or("ARCHIVE_MAX_FILE_BYTES", 8 * 1024 * 1024)
+}
+
+/// Total size the local attachment archive is allowed to grow to, in bytes.
+pub fn get_archive_max_total_bytes() -> u64 {
+    var_or("ARCHIVE_MAX_TOTAL_BYTES", 1024 * 1024 * 1024)
+}
+
+/// Starboard for bestofs from age-restricted channels, if there is one.
+pub fn get_nsfw_starboard_channel_id() -> Option<u64> {
+    var("NSFW_STARBOARD_CHANNEL_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+}
+
+/// Starboard for bestofs from channels most members can't see, if there is one.
+pub fn get_private_starboard_channel_id() -> Option<u64> {
+    var("PRIVATE_STARBOARD_CHANNEL_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+}
+
+/// Lowest net rating a quote can have and still be picked for the daily quote.
+pub fn get_quote_min_daily_score() -> i64 {
+    var_or("QUOTE_MIN_DAILY_SCORE", -3)
+}
+
+/// How similar, from 0 to 1, a new quote's text has to be to a stored one to count as a duplicate.
+pub fn get_quote_duplicate_similarity() -> f64 {
+    var_or("QUOTE_DUPLICATE_SIMILARITY", 0.85)
+}
+
+/// Channel to mention people in about their closed requests when they can't be sent a DM, if
+/// there is one.
+pub fn get_request_notification_channel_id() -> Option<u64> {
+    var("REQUEST_NOTIFICATION_CHANNEL_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+}
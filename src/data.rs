@@ -1,7 +1,13 @@
+pub mod archive;
 pub mod bestof;
+pub mod card;
 pub mod db;
+pub mod markov;
+pub mod quote_files;
 pub mod quotes;
+pub mod render;
 pub mod requests;
+pub mod restrictions;
 
 use crate::events::mentionme::RobotQuotes;
 
@@ -0,0 +1,118 @@
+use crate::constants::{get_archive_max_file_bytes, get_archive_max_total_bytes};
+
+use log::{debug, info, warn};
+use poise::serenity_prelude as serenity;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_DIR: &str = "./data/archive";
+
+/// Download an attachment into the local archive, named by the hash of its contents.
+/// Returns the archived file name, or None if the attachment is too big to keep.
+pub async fn archive_attachment(
+    attachment: &serenity::Attachment,
+) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+    if u64::from(attachment.size) > get_archive_max_file_bytes() {
+        info!(
+            "Not archiving attachment {:?}, it's {:?} bytes",
+            attachment.filename, attachment.size
+        );
+        return Ok(None);
+    }
+
+    let bytes = attachment.download().await?;
+    let name = archived_name(&bytes, &attachment.filename);
+    let path = archived_path(&name);
+
+    // Identical files are only stored once
+    if path.exists() {
+        debug!(
+            "Attachment {:?} is already archived as {:?}",
+            attachment.filename, name
+        );
+        return Ok(Some(name));
+    }
+
+    if archive_size()? + bytes.len() as u64 > get_archive_max_total_bytes() {
+        warn!(
+            "Not archiving attachment {:?}, the archive is full",
+            attachment.filename
+        );
+        return Ok(None);
+    }
+
+    fs::create_dir_all(ARCHIVE_DIR)?;
+    fs::write(&path, &bytes)?;
+    info!(
+        "Archived attachment {:?} as {:?}",
+        attachment.filename, name
+    );
+
+    Ok(Some(name))
+}
+
+/// Check whether a file is present in the archive.
+pub fn is_archived(name: &str) -> bool {
+    archived_path(name).exists()
+}
+
+/// Load an archived file so it can be uploaded again.
+pub async fn load_attachment(name: &str) -> Result<serenity::CreateAttachment, serenity::Error> {
+    serenity::CreateAttachment::path(archived_path(name)).await
+}
+
+/// Delete every archived file that isn't in the referenced set, returning how many were removed.
+pub fn remove_unreferenced(referenced: &HashSet<String>) -> Result<usize, io::Error> {
+    if !Path::new(ARCHIVE_DIR).exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for entry in fs::read_dir(ARCHIVE_DIR)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !referenced.contains(&name) {
+            fs::remove_file(entry.path())?;
+            debug!("Removed unreferenced archived file {:?}", name);
+            removed += 1;
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Content-addressed file name, keeping the original extension so Discord renders it.
+fn archived_name(bytes: &[u8], filename: &str) -> String {
+    let hash = hex::encode(Sha256::digest(bytes));
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()));
+
+    match extension {
+        Some(extension) => format!("{}.{}", hash, extension.to_lowercase()),
+        None => hash,
+    }
+}
+
+fn archived_path(name: &str) -> PathBuf {
+    Path::new(ARCHIVE_DIR).join(name)
+}
+
+/// Total size of everything in the archive, in bytes.
+fn archive_size() -> Result<u64, io::Error> {
+    if !Path::new(ARCHIVE_DIR).exists() {
+        return Ok(0);
+    }
+
+    let mut total = 0;
+    for entry in fs::read_dir(ARCHIVE_DIR)? {
+        total += entry?.metadata()?.len();
+    }
+
+    Ok(total)
+}
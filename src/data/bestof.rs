@@ -1,7 +1,11 @@
-use crate::constants::get_update_channel_id;
-use crate::data::db;
-
-use chrono::{DateTime, Duration, Utc};
+use crate::constants::{
+    get_bestof_nominations_required, get_trending_max_age_hours, get_trending_min_reactions,
+    get_trending_window_minutes, get_update_channel_id, THICC_GUILD_ID,
+};
+use crate::data::restrictions::{self, ChannelRestriction};
+use crate::data::{archive, db, render};
+
+use chrono::{DateTime, Datelike, Duration, Utc};
 use futures::stream::StreamExt;
 use log::{debug, info, warn};
 use poise::serenity_prelude as serenity;
@@ -9,32 +13,39 @@ use poise::serenity_prelude::{ChannelId, Context, Message, MessageId};
 use rand::rngs::{OsRng, StdRng};
 use rand::seq::IteratorRandom;
 use rand::SeedableRng;
-use sqlx::FromRow;
-use std::collections::{HashMap, HashSet};
+use sqlx::{FromRow, Pool, Sqlite};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 const MESSAGES_TO_CHECK: u8 = 100;
 const MINIMUM_REACTIONS: u64 = 5;
+// Discord's JSON error code for a message that doesn't exist
+const UNKNOWN_MESSAGE_CODE: isize = 10008;
+// Discord rejects embed descriptions over 4096 characters
+const MAX_EMBED_CONTENT_LENGTH: usize = 3900;
 
 #[derive(FromRow, Debug, Clone)]
 pub struct BestOfMessage {
     pub id: i64,
     pub author: String,
+    pub author_id: Option<i64>,
     pub content: String,
     pub link: String,
     pub channel: String,
     pub count: i64,
     pub timestamp: f64,
     pub image: Option<String>,
+    pub nominated: bool,
+    pub archived_image: Option<String>,
 }
 
 impl BestOfMessage {
     pub async fn from_serenity_message(
         message: &serenity::Message,
         ctx: &serenity::Context,
-    ) -> Result<Self, Box<dyn Error>> {
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
         let channel_name = match message.channel_id.to_channel(ctx).await? {
             serenity::Channel::Guild(channel) => channel.name.clone(),
             serenity::Channel::Private(_) => "Private Channel".to_string(),
@@ -44,17 +55,83 @@ impl BestOfMessage {
         Ok(BestOfMessage {
             id: message.id.get() as i64,                          // Message ID as i64
             author: message.author.name.clone(),                  // Author's name
+            author_id: Some(message.author.id.get() as i64),      // Author's user ID
             content: message.content.clone(),                     // Message content
             link: message.link(),                                 // Permalink to the message
             channel: channel_name,                                // Channel name
             count: total_number_of_reactions(message),            // Total reaction count as i64
             timestamp: message.timestamp.unix_timestamp() as f64, // Message timestamp
             image: message.attachments.first().map(|a| a.url.clone()), // Optional image URL from the attachments
+            nominated: false,
+            archived_image: None,
         })
     }
 
+    /// Load the archived copy of this message's image, if there is one.
+    pub async fn archived_attachment(&self) -> Option<serenity::CreateAttachment> {
+        if self.has_spoilered_image() {
+            return None;
+        }
+
+        let name = self.archived_image.as_ref()?;
+        match archive::load_attachment(name).await {
+            Ok(attachment) => Some(attachment),
+            Err(why) => {
+                warn!("Failed to load archived image {:?}: {:?}", name, why);
+                None
+            }
+        }
+    }
+
+    /// Insert or update this message in the persisted database.
+    async fn upsert(&self, conn: &Pool<Sqlite>) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO messages (id, author, author_id, content, link, channel, count, timestamp, image, nominated, archived_image)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+             author = excluded.author,
+             author_id = excluded.author_id,
+             content = excluded.content,
+             link = excluded.link,
+             channel = excluded.channel,
+             count = excluded.count,
+             timestamp = excluded.timestamp,
+             image = excluded.image,
+             nominated = excluded.nominated,
+             archived_image = excluded.archived_image",
+        )
+        .bind(self.id)
+        .bind(&self.author)
+        .bind(self.author_id)
+        .bind(&self.content)
+        .bind(&self.link)
+        .bind(&self.channel)
+        .bind(self.count)
+        .bind(self.timestamp)
+        .bind(&self.image)
+        .bind(self.nominated)
+        .bind(&self.archived_image)
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
     /// Create an embed for this message.
-    pub fn create_embed(&self) -> Result<serenity::CreateEmbed, Box<dyn Error + Send + Sync>> {
+    pub async fn create_embed(
+        &self,
+        ctx: &Context,
+    ) -> Result<serenity::CreateEmbed, Box<dyn Error + Send + Sync>> {
+        self.create_embed_with_limit(ctx, MAX_EMBED_CONTENT_LENGTH)
+            .await
+    }
+
+    /// Create an embed for this message, truncating the content to the given length.
+    pub async fn create_embed_with_limit(
+        &self,
+        ctx: &Context,
+        max_content_length: usize,
+    ) -> Result<serenity::CreateEmbed, Box<dyn Error + Send + Sync>> {
         // Handle the timestamp
         let timestamp_result =
             serenity::model::Timestamp::from_unix_timestamp(self.timestamp as i64);
@@ -65,38 +142,217 @@ impl BestOfMessage {
             Err(e) => return Err(Box::new(e)),
         };
 
+        let guild_id = self.guild_id();
+
+        // Show the author's current nickname and avatar, falling back to the stored name
+        let resolved_author = match self.author_id.filter(|id| *id > 0) {
+            Some(id) => {
+                render::resolve_author(ctx, guild_id, serenity::UserId::new(id as u64)).await
+            }
+            None => None,
+        };
+        let author = match &resolved_author {
+            Some((name, avatar)) => {
+                serenity::CreateEmbedAuthor::new(name.clone()).icon_url(avatar.clone())
+            }
+            None => serenity::CreateEmbedAuthor::new(self.author.clone()),
+        };
+        let author_name = resolved_author.map_or(self.author.clone(), |(name, _)| name);
+
+        let footer = if self.nominated {
+            format!("#{} • Nominated", &self.channel)
+        } else {
+            format!("#{}", &self.channel)
+        };
+
         // Initialize the embed with the title and timestamp
         let mut embed = serenity::CreateEmbed::default()
-            .title(format!("Message by {}", self.author))
+            .author(author)
+            .title(format!("Message by {}", author_name))
             .timestamp(timestamp)
             .url(&self.link)
-            .footer(serenity::CreateEmbedFooter::new(format!(
-                "#{}",
-                &self.channel
-            )));
+            .footer(serenity::CreateEmbedFooter::new(footer));
 
-        if let Some(attachment) = &self.image {
-            embed = embed.image(attachment.clone());
+        // Prefer the archived copy, the CDN link expires after a while
+        match (&self.archived_image, &self.image) {
+            _ if self.has_spoilered_image() => {}
+            (Some(archived), _) if archive::is_archived(archived) => {
+                embed = embed.image(format!("attachment://{}", archived));
+            }
+            (_, Some(attachment)) => {
+                embed = embed.image(attachment.clone());
+            }
+            _ => {}
+        }
+
+        let content = render::resolve_mentions(ctx, guild_id, &self.content).await;
+        let mut content = render::truncate_with_link(&content, max_content_length, &self.link);
+
+        // Embed images can't be spoilered, so point at the original instead
+        if self.has_spoilered_image() {
+            content.push_str("\n\n*Spoilered image hidden, see the original message.*");
         }
 
         // Set the description
         embed = embed.description(format!(
             "{}\n\n-----\n*Total Number of Reactions:* {}",
-            self.content, self.count,
+            content, self.count,
         ));
 
         Ok(embed)
     }
+
+    /// Guild the message was posted in, taken from its permalink.
+    fn guild_id(&self) -> Option<serenity::GuildId> {
+        self.id_from_link(2).map(serenity::GuildId::new)
+    }
+
+    /// Channel the message was posted in, taken from its permalink.
+    pub fn channel_id(&self) -> Option<ChannelId> {
+        self.id_from_link(1).map(ChannelId::new)
+    }
+
+    /// Permalinks look like `https://discord.com/channels/<guild>/<channel>/<message>`.
+    fn id_from_link(&self, index_from_end: usize) -> Option<u64> {
+        self.link
+            .split('/')
+            .rev()
+            .nth(index_from_end)
+            .and_then(|id| id.parse::<u64>().ok())
+            .filter(|id| *id > 0)
+    }
+
+    /// Discord marks spoilered attachments by prefixing their file name.
+    fn has_spoilered_image(&self) -> bool {
+        self.image
+            .as_ref()
+            .is_some_and(|url| url.contains("/SPOILER_"))
+    }
+}
+
+/// Aggregated bestof statistics for a single author.
+#[derive(Debug, Clone)]
+pub struct BestOfUserStats {
+    pub total_bestofs: usize,
+    pub total_reactions: i64,
+    pub best_message: BestOfMessage,
+    pub top_channel: String,
+    pub top_channel_bestofs: usize,
+    pub rank: usize,
+    pub ranked_authors: usize,
+    pub per_month: BTreeMap<(i32, u32), usize>,
+}
+
+impl BestOfUserStats {
+    /// Create a stats card embed, using the given display name and avatar.
+    pub fn create_embed(&self, display_name: &str, avatar_url: String) -> serenity::CreateEmbed {
+        let average_reactions = self.total_reactions as f64 / self.total_bestofs as f64;
+
+        // Only show the most recent months so the field stays within Discord's limits
+        let months = self
+            .per_month
+            .iter()
+            .rev()
+            .take(12)
+            .map(|((year, month), count)| format!("{}-{:02}: {}", year, month, count))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        serenity::CreateEmbed::default()
+            .author(serenity::CreateEmbedAuthor::new(display_name).icon_url(avatar_url.clone()))
+            .title(format!("Bestof stats for {}", display_name))
+            .thumbnail(avatar_url)
+            .field("Bestofs", self.total_bestofs.to_string(), true)
+            .field("Total reactions", self.total_reactions.to_string(), true)
+            .field(
+                "Average reactions",
+                format!("{:.1}", average_reactions),
+                true,
+            )
+            .field(
+                "Rank",
+                format!("#{} of {}", self.rank, self.ranked_authors),
+                true,
+            )
+            .field(
+                "Most successful channel",
+                format!("#{} ({})", self.top_channel, self.top_channel_bestofs),
+                true,
+            )
+            .field(
+                "Best message",
+                format!(
+                    "[{} reactions in #{}]({})",
+                    self.best_message.count, self.best_message.channel, self.best_message.link
+                ),
+                false,
+            )
+            .field("Bestofs per month", months, false)
+    }
+}
+
+/// Outcome of nominating a message for bestof.
+#[derive(Debug, Clone)]
+pub enum Nomination {
+    Counted { nominations: i64, required: i64 },
+    AlreadyNominated { nominations: i64, required: i64 },
+    AlreadyBestOf,
+    Approved(BestOfMessage),
+}
+
+/// Reaction counts seen for a recent message, used to measure reaction velocity.
+#[derive(Debug, Clone)]
+struct ReactionHistory {
+    posted: DateTime<Utc>,
+    snapshots: VecDeque<(DateTime<Utc>, i64)>,
+    called_out: bool,
+}
+
+impl ReactionHistory {
+    fn new(posted: DateTime<Utc>, window: Duration) -> ReactionHistory {
+        let mut snapshots = VecDeque::new();
+
+        // A message posted within the window started out with no reactions
+        if posted >= Utc::now() - window {
+            snapshots.push_back((posted, 0));
+        }
+
+        ReactionHistory {
+            posted,
+            snapshots,
+            called_out: false,
+        }
+    }
+
+    /// Record the current count and return how many reactions were gained over the window.
+    fn record(&mut self, now: DateTime<Utc>, count: i64, window: Duration) -> i64 {
+        self.snapshots.push_back((now, count));
+
+        // Measure from the latest snapshot taken before the window started, or the oldest one
+        let window_start = now - window;
+        let baseline = self
+            .snapshots
+            .iter()
+            .rposition(|(taken, _)| *taken <= window_start)
+            .unwrap_or(0);
+        self.snapshots.drain(..baseline);
+
+        count - self.snapshots.front().map_or(count, |(_, count)| *count)
+    }
 }
 
 pub struct BestOf {
     runtime_db: HashMap<i64, BestOfMessage>,
+    reaction_history: HashMap<i64, ReactionHistory>,
+    channel_restrictions: HashMap<ChannelId, ChannelRestriction>,
 }
 
 impl BestOf {
     pub fn new() -> BestOf {
         BestOf {
             runtime_db: HashMap::new(),
+            reaction_history: HashMap::new(),
+            channel_restrictions: HashMap::new(),
         }
     }
 
@@ -109,16 +365,130 @@ impl BestOf {
     ) -> Result<(), Box<dyn Error>> {
         info!("Starting reaction counting..");
 
+        if let Err(why) = self.update_channel_restrictions(ctx).await {
+            warn!("Failed to update channel restrictions: {:?}", why);
+        }
+
         let mut current_messages = count_current_reactions_across_channels(ctx, since).await?;
+
+        let trending_messages = self.update_reaction_history(&current_messages);
+        post_trending(ctx, &self.channel_restrictions, trending_messages).await?;
+
+        // Only messages meeting the bestof criteria go any further
+        for messages in current_messages.values_mut() {
+            messages.retain(message_meets_criteria);
+        }
+        current_messages.retain(|_, messages| !messages.is_empty());
+
         let new_messages = self
             .update_runtime_db_from_new_bestof(ctx, &mut current_messages)
             .await?;
 
-        post_update(ctx, new_messages).await?;
+        post_update(ctx, &self.channel_restrictions, new_messages).await?;
 
         Ok(())
     }
 
+    /// Record the NSFW flag and visibility of every channel in the guild.
+    async fn update_channel_restrictions(&mut self, ctx: &Context) -> Result<(), serenity::Error> {
+        let guild_id = serenity::GuildId::new(THICC_GUILD_ID);
+        let roles = guild_id.roles(&ctx.http).await?;
+        let everyone = roles.get(&serenity::RoleId::new(guild_id.get()));
+
+        for (channel_id, channel) in guild_id.channels(&ctx.http).await? {
+            self.channel_restrictions.insert(
+                channel_id,
+                ChannelRestriction::from_guild_channel(&channel, everyone),
+            );
+        }
+
+        debug!(
+            "Updated channel restrictions {:#?}",
+            self.channel_restrictions
+        );
+        Ok(())
+    }
+
+    /// Pick where a bestof can be reposted, given where we'd like to post it.
+    pub async fn route(
+        &self,
+        ctx: &Context,
+        message: &BestOfMessage,
+        destination: ChannelId,
+    ) -> Option<ChannelId> {
+        match message.channel_id() {
+            Some(source) => {
+                restrictions::route(ctx, &self.channel_restrictions, source, destination).await
+            }
+            // Without its channel there's no telling how restricted it is
+            None => None,
+        }
+    }
+
+    /// Whether a bestof can be reposted in the given channel. Routing only depends on the
+    /// source channel, so the answer for each one is kept in `checked` to save looking it up
+    /// again for the next bestof.
+    async fn can_repost(
+        &self,
+        ctx: &Context,
+        message: &BestOfMessage,
+        destination: ChannelId,
+        checked: &mut HashMap<Option<ChannelId>, bool>,
+    ) -> bool {
+        let source = message.channel_id();
+        if let Some(allowed) = checked.get(&source) {
+            return *allowed;
+        }
+
+        let allowed = self.route(ctx, message, destination).await == Some(destination);
+        checked.insert(source, allowed);
+        allowed
+    }
+
+    /// Snapshot the reaction counts of recent messages and return any that are trending.
+    fn update_reaction_history(
+        &mut self,
+        current_messages: &HashMap<ChannelId, Vec<Message>>,
+    ) -> Vec<Message> {
+        let now = Utc::now();
+        let window = Duration::minutes(get_trending_window_minutes());
+        let max_age = Duration::hours(get_trending_max_age_hours());
+        let min_reactions = get_trending_min_reactions();
+
+        // Forget about messages that are too old to trend
+        self.reaction_history
+            .retain(|_, history| history.posted >= now - max_age);
+
+        let mut trending_messages = Vec::new();
+        for msg in current_messages.values().flatten() {
+            let posted =
+                DateTime::from_timestamp(msg.timestamp.unix_timestamp(), 0).unwrap_or_default();
+            if posted < now - max_age {
+                continue;
+            }
+
+            let key = msg.id.get() as i64;
+            let history = self
+                .reaction_history
+                .entry(key)
+                .or_insert_with(|| ReactionHistory::new(posted, window));
+            let gained = history.record(now, total_number_of_reactions(msg), window);
+
+            // Only call out messages that haven't already made it to bestof
+            if gained >= min_reactions
+                && !history.called_out
+                && !self.runtime_db.contains_key(&key)
+                && !message_meets_criteria(msg)
+            {
+                debug!("Message {:?} gained {:?} reactions, trending", key, gained);
+                history.called_out = true;
+                trending_messages.push(msg.clone());
+            }
+        }
+
+        trending_messages
+    }
+
     /// Translate a message update into the runtime database.
     async fn update_runtime_db_from_new_bestof(
         &mut self,
@@ -160,7 +530,7 @@ impl BestOf {
         let mut new_messages_for_channel = Vec::new();
 
         for msg in messages.drain(..) {
-            let value = match BestOfMessage::from_serenity_message(&msg, ctx).await {
+            let mut value = match BestOfMessage::from_serenity_message(&msg, ctx).await {
                 Ok(value) => value,
                 Err(why) => {
                     warn!("Failed to convert message {:#?}: {:#?}", msg, why);
@@ -170,6 +540,15 @@ impl BestOf {
 
             let key = value.id;
 
+            match self.runtime_db.get(&key) {
+                // Keep what we already know about messages we've seen before
+                Some(existing) => {
+                    value.nominated = existing.nominated;
+                    value.archived_image = existing.archived_image.clone();
+                }
+                None => value.archived_image = archive_image(&msg).await,
+            }
+
             if self.runtime_db.insert(key, value.clone()).is_none() {
                 // This is a new insertion
                 debug!("Added new message id {:?}", key);
@@ -195,6 +574,15 @@ impl BestOf {
             self.runtime_db.insert(msg.id, msg);
         }
 
+        let restrictions: Vec<ChannelRestriction> = persist_db
+            .load_all_from_table(String::from("channel_restrictions"))
+            .await?;
+
+        for restriction in restrictions {
+            self.channel_restrictions
+                .insert(ChannelId::new(restriction.id as u64), restriction);
+        }
+
         Ok(())
     }
 
@@ -207,42 +595,158 @@ impl BestOf {
 
         // upsert all messages in runtime_db into the persisted database
         for msg in self.runtime_db.values() {
+            msg.upsert(db_conn).await?;
+        }
+
+        for restriction in self.channel_restrictions.values() {
             sqlx::query(
-                "INSERT INTO messages (id, author, content, link, channel, count, timestamp, image)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                "INSERT INTO channel_restrictions (id, nsfw, public) VALUES (?, ?, ?)
              ON CONFLICT(id) DO UPDATE SET
-             author = excluded.author,
-             content = excluded.content,
-             link = excluded.link,
-             channel = excluded.channel,
-             count = excluded.count,
-             timestamp = excluded.timestamp,
-             image = excluded.image",
+             nsfw = excluded.nsfw,
+             public = excluded.public",
             )
-            .bind(msg.id)
-            .bind(&msg.author)
-            .bind(&msg.content)
-            .bind(&msg.link)
-            .bind(&msg.channel)
-            .bind(msg.count)
-            .bind(msg.timestamp)
-            .bind(&msg.image)
+            .bind(restriction.id)
+            .bind(restriction.nsfw)
+            .bind(restriction.public)
             .execute(db_conn)
             .await?;
         }
         Ok(())
     }
 
-    /// Return an embed of a random message from the runtime db.
-    pub async fn get_random_bestof_embed(
+    /// Record a nomination for a message, adding it as a manual bestof once enough distinct
+    /// users have nominated it or an owner approves it.
+    pub async fn nominate(
+        &mut self,
+        ctx: &Context,
+        persist_db: &db::BotDatabase,
+        message: &Message,
+        user_id: serenity::UserId,
+        approve: bool,
+    ) -> Result<Nomination, Box<dyn Error + Send + Sync>> {
+        let key = message.id.get() as i64;
+        if self.runtime_db.contains_key(&key) {
+            return Ok(Nomination::AlreadyBestOf);
+        }
+
+        let db_conn = persist_db.get_conn();
+
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO bestof_nominations (message_id, user_id) VALUES (?, ?)",
+        )
+        .bind(key)
+        .bind(user_id.get() as i64)
+        .execute(db_conn)
+        .await?
+        .rows_affected();
+
+        let (nominations,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM bestof_nominations WHERE message_id = ?")
+                .bind(key)
+                .fetch_one(db_conn)
+                .await?;
+        let required = get_bestof_nominations_required();
+
+        if !approve && nominations < required {
+            return Ok(match inserted {
+                0 => Nomination::AlreadyNominated {
+                    nominations,
+                    required,
+                },
+                _ => Nomination::Counted {
+                    nominations,
+                    required,
+                },
+            });
+        }
+
+        let mut bestof = BestOfMessage::from_serenity_message(message, ctx).await?;
+        bestof.nominated = true;
+        bestof.archived_image = archive_image(message).await;
+        bestof.upsert(db_conn).await?;
+
+        sqlx::query("DELETE FROM bestof_nominations WHERE message_id = ?")
+            .bind(key)
+            .execute(db_conn)
+            .await?;
+
+        info!("Added nominated message {:?} as a bestof", key);
+        self.runtime_db.insert(key, bestof.clone());
+
+        Ok(Nomination::Approved(bestof))
+    }
+
+    /// Return a random message from the runtime db that can be reposted in the given channel,
+    /// or None if there isn't one.
+    pub async fn get_random_bestof(
         &self,
-    ) -> Result<serenity::CreateEmbed, Box<dyn Error + Send + Sync>> {
+        ctx: &Context,
+        destination: ChannelId,
+    ) -> Result<Option<BestOfMessage>, Box<dyn Error + Send + Sync>> {
         let mut rng = StdRng::from_rng(OsRng)?;
 
-        match self.runtime_db.values().choose(&mut rng) {
-            None => Err("No messages available".into()), // Handle empty runtime_db case
-            Some(msg) => msg.create_embed(),
+        let mut checked = HashMap::new();
+        let mut candidates = Vec::new();
+        for msg in self.runtime_db.values() {
+            if self.can_repost(ctx, msg, destination, &mut checked).await {
+                candidates.push(msg);
+            }
+        }
+        debug!(
+            "{} of {} bestofs can be posted in {:?}",
+            candidates.len(),
+            self.runtime_db.len(),
+            destination
+        );
+
+        Ok(candidates.into_iter().choose(&mut rng).cloned())
+    }
+
+    /// Every bestof in the runtime db.
+    pub fn get_messages(&self) -> Vec<BestOfMessage> {
+        self.runtime_db.values().cloned().collect()
+    }
+
+    /// Stop referencing a bestof's archived image, in both the runtime and persisted db, so
+    /// the next cleanup deletes the file. The bestof itself is kept.
+    pub async fn forget_archived_image(
+        &mut self,
+        persist_db: &db::BotDatabase,
+        id: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE messages SET archived_image = NULL WHERE id = ?")
+            .bind(id)
+            .execute(persist_db.get_conn())
+            .await?;
+
+        if let Some(bestof) = self.runtime_db.get_mut(&id) {
+            bestof.archived_image = None;
+        }
+        info!("Forgot archived image of bestof {:?}", id);
+        Ok(())
+    }
+
+    /// Archive the image of a bestof added before it would have been, from a fresh copy of its
+    /// message since the attachment URL we stored has expired. Returns the archived file name.
+    pub async fn backfill_archived_image(&mut self, message: &Message) -> Option<String> {
+        let key = message.id.get() as i64;
+        if self.runtime_db.get(&key)?.archived_image.is_some() {
+            return None;
+        }
+
+        let name = archive_image(message).await?;
+        if let Some(bestof) = self.runtime_db.get_mut(&key) {
+            bestof.archived_image = Some(name.clone());
         }
+        Some(name)
+    }
+
+    /// Names of every archived image still referenced by a bestof.
+    pub fn get_archived_images(&self) -> HashSet<String> {
+        self.runtime_db
+            .values()
+            .filter_map(|msg| msg.archived_image.clone())
+            .collect()
     }
 
     /// Top 10 most reacted messages, optionally filtered.
@@ -252,6 +756,7 @@ impl BestOf {
         user_id: Option<serenity::UserId>,
         channel_id: Option<serenity::ChannelId>,
         time_filter: Option<String>,
+        destination: serenity::ChannelId,
     ) -> Result<Vec<BestOfMessage>, Box<dyn Error + Send + Sync>> {
         let mut top_messages: Vec<BestOfMessage> = self.runtime_db.values().cloned().collect();
 
@@ -319,7 +824,70 @@ impl BestOf {
         }
 
         top_messages.sort_by(|a, b| b.count.cmp(&a.count));
-        Ok(top_messages.into_iter().take(10).collect())
+
+        // Leave out anything that can't be reposted where we were asked
+        let mut repostable = Vec::new();
+        let mut checked = HashMap::new();
+        for msg in top_messages {
+            if repostable.len() == 10 {
+                break;
+            }
+            if self.can_repost(ctx, &msg, destination, &mut checked).await {
+                repostable.push(msg);
+            }
+        }
+
+        Ok(repostable)
+    }
+
+    /// Aggregate the bestof statistics for a single author, if they have any bestofs.
+    pub fn get_user_stats(&self, author: &str) -> Option<BestOfUserStats> {
+        let messages: Vec<&BestOfMessage> = self
+            .runtime_db
+            .values()
+            .filter(|msg| msg.author == author)
+            .collect();
+
+        let best_message = (*messages.iter().max_by_key(|msg| msg.count)?).clone();
+
+        let mut per_channel: HashMap<&str, usize> = HashMap::new();
+        let mut per_month = BTreeMap::new();
+        for msg in &messages {
+            *per_channel.entry(msg.channel.as_str()).or_insert(0) += 1;
+
+            if let Some(posted) = DateTime::from_timestamp(msg.timestamp as i64, 0) {
+                *per_month
+                    .entry((posted.year(), posted.month()))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        let (top_channel, top_channel_bestofs) = per_channel
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(channel, count)| (channel.to_string(), count))?;
+
+        // Rank every author by how many bestofs they have
+        let mut per_author: HashMap<&str, usize> = HashMap::new();
+        for msg in self.runtime_db.values() {
+            *per_author.entry(msg.author.as_str()).or_insert(0) += 1;
+        }
+        let rank = per_author
+            .values()
+            .filter(|count| **count > messages.len())
+            .count()
+            + 1;
+
+        Some(BestOfUserStats {
+            total_bestofs: messages.len(),
+            total_reactions: messages.iter().map(|msg| msg.count).sum(),
+            best_message,
+            top_channel,
+            top_channel_bestofs,
+            rank,
+            ranked_authors: per_author.len(),
+            per_month,
+        })
     }
 }
 
@@ -329,7 +897,7 @@ async fn count_current_reactions_across_channels(
     ctx: &Context,
     since: Option<DateTime<Utc>>,
 ) -> Result<HashMap<ChannelId, Vec<Message>>, Box<dyn Error>> {
-    let thicc_guild = serenity::GuildId::new(561602796286378029);
+    let thicc_guild = serenity::GuildId::new(THICC_GUILD_ID);
 
     let reacted_messages_per_channel = Arc::new(Mutex::new(HashMap::new()));
     let mut channel_denylist = HashSet::new();
@@ -483,25 +1051,25 @@ async fn get_message_batch(
     }
 }
 
-/// Filter messages that meet the criteria.
+/// Filter messages that have been reacted to by anyone.
 async fn get_reacted_messages(retrieved_messages: &mut Vec<Message>) -> Vec<Message> {
     let mut reacted_messages: Vec<Message> = Vec::new();
     for message in retrieved_messages.drain(..) {
-        match message_meets_criteria(message) {
-            None => continue,
-            Some(message) => reacted_messages.push(message),
+        if message.author.bot || message.reactions.is_empty() {
+            continue;
         }
+        reacted_messages.push(message);
     }
     reacted_messages
 }
 
-/// Check if a message meets the criteria.
-fn message_meets_criteria(message: Message) -> Option<Message> {
+/// Check if a message meets the bestof criteria.
+fn message_meets_criteria(message: &Message) -> bool {
     if message.author.bot
         || message.reactions.is_empty()
-        || number_of_users_reacted(&message) < MINIMUM_REACTIONS
+        || number_of_users_reacted(message) < MINIMUM_REACTIONS
     {
-        return None;
+        return false;
     }
 
     debug!(
@@ -509,7 +1077,7 @@ fn message_meets_criteria(message: Message) -> Option<Message> {
         message
     );
 
-    Some(message)
+    true
 }
 
 /// Takes a Message and extracts the highest count reaction.
@@ -532,15 +1100,22 @@ fn number_of_users_reacted(message: &Message) -> u64 {
 /// Post an update to the channel.
 async fn post_update(
     ctx: &Context,
+    channel_restrictions: &HashMap<ChannelId, ChannelRestriction>,
     new_messages: Vec<BestOfMessage>,
 ) -> Result<(), Box<dyn Error>> {
     let update_channel = ChannelId::new(get_update_channel_id());
 
     for msg in new_messages {
+        let Some(channel) =
+            route_new_message(ctx, channel_restrictions, &msg, update_channel).await
+        else {
+            continue;
+        };
+
         match post_message_as_embed(
             ctx,
             &msg,
-            update_channel,
+            channel,
             Some(String::from("*Found and stored this bestof:*")),
         )
         .await
@@ -553,6 +1128,68 @@ async fn post_update(
     Ok(())
 }
 
+/// Post a callout for messages that are gaining reactions quickly.
+async fn post_trending(
+    ctx: &Context,
+    channel_restrictions: &HashMap<ChannelId, ChannelRestriction>,
+    trending_messages: Vec<Message>,
+) -> Result<(), Box<dyn Error>> {
+    let update_channel = ChannelId::new(get_update_channel_id());
+
+    for msg in trending_messages {
+        let trending = match BestOfMessage::from_serenity_message(&msg, ctx).await {
+            Ok(trending) => trending,
+            Err(why) => {
+                warn!("Failed to convert trending message {:?}: {:?}", msg.id, why);
+                continue;
+            }
+        };
+
+        let Some(channel) =
+            route_new_message(ctx, channel_restrictions, &trending, update_channel).await
+        else {
+            continue;
+        };
+
+        if let Err(why) = post_message_as_embed(
+            ctx,
+            &trending,
+            channel,
+            Some(String::from("*🔥 Trending right now:*")),
+        )
+        .await
+        {
+            warn!("Failed to send trending callout: {:?}", why);
+        }
+    }
+
+    Ok(())
+}
+
+/// Route a newly found message, logging when there's nowhere it can be posted.
+async fn route_new_message(
+    ctx: &Context,
+    channel_restrictions: &HashMap<ChannelId, ChannelRestriction>,
+    message: &BestOfMessage,
+    destination: ChannelId,
+) -> Option<ChannelId> {
+    let Some(source) = message.channel_id() else {
+        warn!(
+            "Not reposting message {:?} without a channel in its link",
+            message.id
+        );
+        return None;
+    };
+    let channel = restrictions::route(ctx, channel_restrictions, source, destination).await;
+    if channel.is_none() {
+        info!(
+            "Not reposting message {:?} from restricted channel #{}",
+            message.id, message.channel
+        );
+    }
+    channel
+}
+
 /// Post a message as an embed to a channel.
 pub async fn post_message_as_embed(
     ctx: &Context,
@@ -560,9 +1197,13 @@ pub async fn post_message_as_embed(
     channel_to_post_to: ChannelId,
     prelude: Option<String>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let embed = message.create_embed()?;
+    let embed = message.create_embed(ctx).await?;
     let mut msg = serenity::CreateMessage::new().embed(embed);
 
+    if let Some(attachment) = message.archived_attachment().await {
+        msg = msg.add_file(attachment);
+    }
+
     if let Some(content) = prelude {
         msg = msg.content(content);
     }
@@ -572,6 +1213,46 @@ pub async fn post_message_as_embed(
     Ok(())
 }
 
+/// Fetch the message a bestof was made from. Returns None if Discord says the message has been
+/// deleted. A missing or inaccessible channel is an error, since it says nothing about the
+/// message itself.
+pub async fn fetch_source_message(
+    ctx: &Context,
+    bestof: &BestOfMessage,
+) -> Result<Option<Message>, serenity::Error> {
+    let channel_id = bestof
+        .channel_id()
+        .ok_or(serenity::Error::Other("Bestof link has no channel"))?;
+
+    match channel_id
+        .message(ctx, MessageId::new(bestof.id as u64))
+        .await
+    {
+        Ok(message) => Ok(Some(message)),
+        Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+            if response.error.code == UNKNOWN_MESSAGE_CODE =>
+        {
+            Ok(None)
+        }
+        Err(why) => Err(why),
+    }
+}
+
+/// Archive the first attachment of a message, which is the one shown in the embed.
+async fn archive_image(message: &Message) -> Option<String> {
+    let attachment = message.attachments.first()?;
+    match archive::archive_attachment(attachment).await {
+        Ok(name) => name,
+        Err(why) => {
+            warn!(
+                "Failed to archive attachment of {:?}: {:?}",
+                message.id, why
+            );
+            None
+        }
+    }
+}
+
 /// Takes a Message and extracts the total count of reactions.
 fn total_number_of_reactions(message: &Message) -> i64 {
     let mut total: i64 = 0;
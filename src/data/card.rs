@@ -0,0 +1,282 @@
+use std::io::Cursor;
+
+use crate::data::quotes::QuoteMessage;
+use crate::data::render::resolve_mentions;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use chrono::DateTime;
+use image::imageops::{self, FilterType};
+use image::{ImageFormat, Rgba, RgbaImage};
+use log::warn;
+use poise::serenity_prelude as serenity;
+use tokio::time::{timeout, Duration};
+
+const QUOTE_FONT: &[u8] = include_bytes!("../../data/fonts/DejaVuSerif-Italic.ttf");
+const ATTRIBUTION_FONT: &[u8] = include_bytes!("../../data/fonts/DejaVuSans-Bold.ttf");
+
+const CARD_WIDTH: u32 = 1000;
+const PADDING: u32 = 60;
+const ACCENT_WIDTH: u32 = 10;
+const AVATAR_SIZE: u32 = 96;
+// Rather draw the card without an avatar than keep the command waiting on the CDN
+const AVATAR_TIMEOUT: Duration = Duration::from_secs(3);
+// Longer quotes are cut off so the card stays a reasonable size
+const MAX_QUOTE_CHARS: usize = 900;
+
+const BACKGROUND: Rgba<u8> = Rgba([30, 31, 34, 255]);
+const GOLD: Rgba<u8> = Rgba([241, 196, 15, 255]);
+const TEXT: Rgba<u8> = Rgba([242, 243, 245, 255]);
+const MUTED: Rgba<u8> = Rgba([148, 155, 164, 255]);
+
+/// Render a quote as a PNG card, with the author's avatar if they're a known Discord user.
+pub async fn render_quote_card(
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    quote: &QuoteMessage,
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let text = resolve_mentions(ctx, Some(guild_id), &quote.quote).await;
+
+    let avatar = match quote.linked_user_id {
+        Some(user_id) => fetch_avatar(ctx, serenity::UserId::new(user_id as u64)).await,
+        None => None,
+    };
+
+    // Prefer when the quote was said over when it was added
+    let date = quote
+        .timestamp
+        .or(quote.created_at)
+        .and_then(|ts| DateTime::from_timestamp(ts as i64, 0))
+        .map(|date| date.format("%B %-d, %Y").to_string());
+
+    Ok(draw_card(&text, &quote.author, date.as_deref(), avatar)?)
+}
+
+/// Download a user's avatar, if they have one we can load.
+async fn fetch_avatar(ctx: &serenity::Context, user_id: serenity::UserId) -> Option<RgbaImage> {
+    let user = user_id.to_user(ctx).await.ok()?;
+
+    let download = async {
+        let url = user.static_face();
+        let avatar = timeout(AVATAR_TIMEOUT, serenity::CreateAttachment::url(ctx, &url)).await??;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(image::load_from_memory(&avatar.data)?)
+    };
+    match download.await {
+        Ok(avatar) => Some(avatar.to_rgba8()),
+        Err(why) => {
+            warn!("Failed to load avatar for {}: {:?}", user_id, why);
+            None
+        }
+    }
+}
+
+fn draw_card(
+    text: &str,
+    author: &str,
+    date: Option<&str>,
+    avatar: Option<RgbaImage>,
+) -> Result<Vec<u8>, image::ImageError> {
+    let quote_font = FontRef::try_from_slice(QUOTE_FONT).expect("embedded quote font is valid");
+    let attribution_font =
+        FontRef::try_from_slice(ATTRIBUTION_FONT).expect("embedded attribution font is valid");
+
+    let mut text = text.trim().to_string();
+    if text.chars().count() > MAX_QUOTE_CHARS {
+        text = text.chars().take(MAX_QUOTE_CHARS).collect::<String>() + "…";
+    }
+    let text = format!("“{}”", text);
+
+    // Shrink the text as quotes get longer
+    let quote_size = match text.chars().count() {
+        0..=120 => 48.0,
+        121..=320 => 38.0,
+        _ => 30.0,
+    };
+    let text_left = (ACCENT_WIDTH + PADDING) as f32;
+    let text_width = (CARD_WIDTH - ACCENT_WIDTH - 2 * PADDING) as f32;
+    let lines = wrap_text(&quote_font, quote_size, &text, text_width);
+    let line_height = quote_size * 1.35;
+
+    let quote_height = (lines.len() as f32 * line_height).ceil() as u32;
+    let footer_top = PADDING + quote_height + PADDING / 2;
+    let height = footer_top + AVATAR_SIZE + PADDING;
+
+    let mut canvas = RgbaImage::from_pixel(CARD_WIDTH, height, BACKGROUND);
+    for y in 0..height {
+        for x in 0..ACCENT_WIDTH {
+            canvas.put_pixel(x, y, GOLD);
+        }
+    }
+
+    let quote_ascent = quote_font.as_scaled(PxScale::from(quote_size)).ascent();
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = PADDING as f32 + quote_ascent + i as f32 * line_height;
+        draw_text(
+            &mut canvas,
+            &quote_font,
+            quote_size,
+            TEXT,
+            text_left,
+            baseline,
+            line,
+        );
+    }
+
+    // Attribution, next to the avatar when there is one
+    let mut attribution_left = text_left;
+    if let Some(avatar) = avatar {
+        let avatar = circle_crop(&imageops::resize(
+            &avatar,
+            AVATAR_SIZE,
+            AVATAR_SIZE,
+            FilterType::Triangle,
+        ));
+        imageops::overlay(&mut canvas, &avatar, text_left as i64, footer_top as i64);
+        attribution_left += (AVATAR_SIZE + PADDING / 3) as f32;
+    }
+
+    let author_size = 32.0;
+    let date_size = 24.0;
+    let author_baseline = match date {
+        Some(_) => footer_top as f32 + AVATAR_SIZE as f32 / 2.0 - 4.0,
+        None => footer_top as f32 + (AVATAR_SIZE as f32 + author_size * 0.7) / 2.0,
+    };
+    draw_text(
+        &mut canvas,
+        &attribution_font,
+        author_size,
+        GOLD,
+        attribution_left,
+        author_baseline,
+        &format!("— {}", author),
+    );
+    if let Some(date) = date {
+        draw_text(
+            &mut canvas,
+            &attribution_font,
+            date_size,
+            MUTED,
+            attribution_left,
+            author_baseline + date_size * 1.5,
+            date,
+        );
+    }
+
+    let mut png = Vec::new();
+    canvas.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// Break text into lines no wider than `max_width`, keeping the line breaks it already has and
+/// splitting words that don't fit on a line by themselves.
+fn wrap_text(font: &FontRef, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = match line.is_empty() {
+                true => word.to_string(),
+                false => format!("{} {}", line, word),
+            };
+            if text_width(font, size, &candidate) <= max_width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if text_width(font, size, &line) > max_width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::take(&mut line));
+                    line.push(c);
+                }
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn text_width(font: &FontRef, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+
+    width
+}
+
+/// Draw a single line of text with its baseline at `baseline`.
+fn draw_text(
+    canvas: &mut RgbaImage,
+    font: &FontRef,
+    size: f32,
+    color: Rgba<u8>,
+    left: f32,
+    baseline: f32,
+    text: &str,
+) {
+    let scaled = font.as_scaled(PxScale::from(size));
+
+    let mut caret = left;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        let glyph = id.with_scale_and_position(size, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        previous = Some(id);
+
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|x, y, coverage| {
+            let x = bounds.min.x as i64 + x as i64;
+            let y = bounds.min.y as i64 + y as i64;
+            if x < 0 || y < 0 || x >= canvas.width() as i64 || y >= canvas.height() as i64 {
+                return;
+            }
+            blend(canvas.get_pixel_mut(x as u32, y as u32), color, coverage);
+        });
+    }
+}
+
+/// Mix `color` over a pixel by `coverage`, from 0 to 1.
+fn blend(pixel: &mut Rgba<u8>, color: Rgba<u8>, coverage: f32) {
+    let alpha = coverage.clamp(0.0, 1.0) * color[3] as f32 / 255.0;
+    for channel in 0..3 {
+        pixel[channel] =
+            (pixel[channel] as f32 * (1.0 - alpha) + color[channel] as f32 * alpha).round() as u8;
+    }
+}
+
+/// Make everything outside the inscribed circle transparent, with a smoothed edge.
+fn circle_crop(image: &RgbaImage) -> RgbaImage {
+    let mut cropped = image.clone();
+    let radius = image.width().min(image.height()) as f32 / 2.0;
+
+    for (x, y, pixel) in cropped.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+        pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+    }
+
+    cropped
+}
@@ -0,0 +1,178 @@
+use std::collections::{HashMap, HashSet};
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+// Number of previous words each word is chosen from
+const ORDER: usize = 2;
+// Walks to try before settling for one that copies a trained text word for word
+const MAX_ATTEMPTS: usize = 10;
+
+/// A word-level Markov chain that can be trained one text at a time.
+#[derive(Debug, Clone, Default)]
+pub struct MarkovChain {
+    /// The words seen after each run of `ORDER` words. Empty strings pad the start of a text
+    /// and mark its end.
+    transitions: HashMap<Vec<String>, Vec<String>>,
+    /// Every text trained on, normalized, to tell when a walk just repeats one.
+    sources: HashSet<String>,
+}
+
+impl MarkovChain {
+    /// Add a text to the chain.
+    pub fn train(&mut self, text: &str) {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            return;
+        }
+
+        let mut state = vec![String::new(); ORDER];
+        for word in words.iter().copied().chain(std::iter::once("")) {
+            self.transitions
+                .entry(state.clone())
+                .or_default()
+                .push(word.to_string());
+            state.remove(0);
+            state.push(word.to_string());
+        }
+
+        self.sources.insert(normalize(text));
+    }
+
+    /// Generate a text of up to `max_words` words, or `None` if the chain hasn't been trained.
+    /// Walks that repeat a trained text are retried a few times, but with too little to go
+    /// on one may still be returned.
+    pub fn generate<R: Rng>(&self, rng: &mut R, max_words: usize) -> Option<String> {
+        if self.transitions.is_empty() {
+            return None;
+        }
+
+        let mut text = String::new();
+        for _ in 0..MAX_ATTEMPTS {
+            text = self.walk(rng, max_words);
+            if !self.sources.contains(&normalize(&text)) {
+                break;
+            }
+        }
+
+        Some(text)
+    }
+
+    fn walk<R: Rng>(&self, rng: &mut R, max_words: usize) -> String {
+        let mut state = vec![String::new(); ORDER];
+        let mut words = Vec::new();
+
+        while words.len() < max_words {
+            let Some(word) = self
+                .transitions
+                .get(&state)
+                .and_then(|next| next.choose(rng))
+            else {
+                break;
+            };
+            if word.is_empty() {
+                break;
+            }
+
+            words.push(word.clone());
+            state.remove(0);
+            state.push(word.clone());
+        }
+
+        words.join(" ")
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(7)
+    }
+
+    #[test]
+    fn untrained_chain_generates_nothing() {
+        let mut chain = MarkovChain::default();
+        assert_eq!(chain.generate(&mut rng(), 10), None);
+
+        chain.train("   ");
+        assert_eq!(chain.generate(&mut rng(), 10), None);
+    }
+
+    #[test]
+    fn single_text_can_only_be_repeated() {
+        let mut chain = MarkovChain::default();
+        chain.train("the cat sat on the mat");
+        assert_eq!(
+            chain.generate(&mut rng(), 20).as_deref(),
+            Some("the cat sat on the mat")
+        );
+    }
+
+    #[test]
+    fn generated_text_is_cut_at_max_words() {
+        let mut chain = MarkovChain::default();
+        chain.train("one two three four five six");
+        assert_eq!(
+            chain.generate(&mut rng(), 3).as_deref(),
+            Some("one two three")
+        );
+    }
+
+    #[test]
+    fn every_word_follows_the_words_before_it_in_training() {
+        let texts = [
+            "i like green eggs and ham",
+            "i like to eat apples and bananas",
+            "you like green apples",
+        ];
+        let mut chain = MarkovChain::default();
+        for text in texts {
+            chain.train(text);
+        }
+
+        let mut rng = rng();
+        for _ in 0..50 {
+            let text = chain.generate(&mut rng, 20).unwrap();
+            let words: Vec<&str> = text.split_whitespace().collect();
+            assert!(!words.is_empty());
+
+            let mut padded = vec![""; ORDER];
+            padded.extend(&words);
+            for window in padded.windows(ORDER + 1) {
+                let state: Vec<String> = window[..ORDER].iter().map(|w| w.to_string()).collect();
+                assert!(
+                    chain.transitions[&state].contains(&window[ORDER].to_string()),
+                    "{:?} never followed {:?}",
+                    window[ORDER],
+                    state
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn walks_prefer_new_texts_over_copies() {
+        let mut chain = MarkovChain::default();
+        chain.train("i like green eggs");
+        chain.train("you like green apples");
+
+        let mut rng = rng();
+        let texts: HashSet<String> = (0..20)
+            .map(|_| chain.generate(&mut rng, 10).unwrap())
+            .collect();
+        assert!(texts.contains("i like green apples") || texts.contains("you like green eggs"));
+        assert!(!texts.contains("i like green eggs"));
+        assert!(!texts.contains("you like green apples"));
+    }
+}
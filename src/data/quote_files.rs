@@ -0,0 +1,375 @@
+use crate::data::quotes::{NewQuote, QuoteLine, QuoteMessage};
+
+use chrono::{DateTime, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+// Same limits as quotes saved from messages
+const MAX_QUOTE_LENGTH: usize = 4000;
+const MAX_AUTHOR_LENGTH: usize = 100;
+
+/// File formats quotes can be imported from and exported to.
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum QuoteFileFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+impl QuoteFileFormat {
+    /// Work out the format of a file from its name.
+    pub fn from_filename(filename: &str) -> Option<QuoteFileFormat> {
+        let extension = filename.rsplit_once('.')?.1.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(QuoteFileFormat::Csv),
+            "json" => Some(QuoteFileFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            QuoteFileFormat::Csv => "csv",
+            QuoteFileFormat::Json => "json",
+        }
+    }
+}
+
+/// A quote as it appears in an import or export file. Only `quote` and `author` are required.
+/// Dates are exported in RFC 3339, and can also be imported as plain `YYYY-MM-DD` dates or unix
+/// timestamps. The lines of a conversation are a list of `{speaker, line}` objects, written as
+/// a JSON array in CSV files.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuoteRecord {
+    pub id: Option<i32>,
+    pub quote: String,
+    pub author: String,
+    pub author_user_id: Option<i64>,
+    pub said_at: Option<RecordDate>,
+    pub source_link: Option<String>,
+    pub submitted_by: Option<i64>,
+    pub added_at: Option<RecordDate>,
+    pub lines: Option<RecordLines>,
+}
+
+/// A date in an import or export file, either written out or as a unix timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordDate {
+    Timestamp(f64),
+    Text(String),
+}
+
+/// The lines of a conversation in an import or export file, either as a list or encoded as a
+/// JSON array.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecordLines {
+    List(Vec<QuoteLine>),
+    Text(String),
+}
+
+impl QuoteRecord {
+    pub fn from_quote(quote: &QuoteMessage) -> QuoteRecord {
+        QuoteRecord {
+            id: Some(quote.id),
+            quote: quote.quote.clone(),
+            author: quote.author.clone(),
+            author_user_id: quote.author_user_id,
+            said_at: quote.timestamp.and_then(format_date),
+            source_link: quote.source_link.clone(),
+            submitted_by: quote.submitted_by,
+            added_at: quote.created_at.and_then(format_date),
+            lines: match quote.lines.is_empty() {
+                true => None,
+                false => Some(RecordLines::List(quote.lines.clone())),
+            },
+        }
+    }
+
+    /// Check the record and turn it into a quote for the guild. Records that don't say who
+    /// added them are credited to `imported_by`. Any ID in the record is ignored.
+    pub fn into_new_quote(self, guild_id: i64, imported_by: i64) -> Result<NewQuote, String> {
+        let quote = self.quote.trim().to_string();
+        let author = self.author.trim().to_string();
+        if quote.is_empty() {
+            return Err("missing quote".to_string());
+        }
+        if quote.chars().count() > MAX_QUOTE_LENGTH {
+            return Err(format!("quote is over {} characters", MAX_QUOTE_LENGTH));
+        }
+        if author.is_empty() {
+            return Err("missing author".to_string());
+        }
+        if author.chars().count() > MAX_AUTHOR_LENGTH {
+            return Err(format!("author is over {} characters", MAX_AUTHOR_LENGTH));
+        }
+
+        let source_link = self.source_link.filter(|link| !link.trim().is_empty());
+        if let Some(link) = &source_link {
+            if !link.starts_with("https://") {
+                return Err(format!("source link \"{}\" isn't a https link", link));
+            }
+        }
+
+        Ok(NewQuote {
+            quote,
+            author,
+            author_user_id: self.author_user_id,
+            timestamp: parse_date(self.said_at)?,
+            source_link,
+            submitted_by: self.submitted_by.unwrap_or(imported_by),
+            created_at: parse_date(self.added_at)?,
+            guild_id,
+            lines: parse_lines(self.lines)?,
+        })
+    }
+}
+
+/// Read the quotes in a file. Each row is read separately so every bad row can be reported;
+/// the outer error is for files that can't be read at all.
+pub fn read_records(
+    format: QuoteFileFormat,
+    contents: &[u8],
+) -> Result<Vec<Result<QuoteRecord, String>>, String> {
+    match format {
+        QuoteFileFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(contents);
+            let headers = reader.headers().map_err(|why| why.to_string())?;
+            if !headers.iter().any(|header| header == "quote")
+                || !headers.iter().any(|header| header == "author")
+            {
+                return Err("the header row needs `quote` and `author` columns".to_string());
+            }
+
+            Ok(reader
+                .deserialize::<QuoteRecord>()
+                .map(|row| row.map_err(|why| why.to_string()))
+                .collect())
+        }
+        QuoteFileFormat::Json => {
+            let rows: Vec<serde_json::Value> =
+                serde_json::from_slice(contents).map_err(|why| why.to_string())?;
+
+            Ok(rows
+                .into_iter()
+                .map(|row| serde_json::from_value(row).map_err(|why| why.to_string()))
+                .collect())
+        }
+    }
+}
+
+/// Write quotes to a file in the given format.
+pub fn write_records(
+    format: QuoteFileFormat,
+    records: &[QuoteRecord],
+) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    match format {
+        QuoteFileFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Vec::new());
+            for record in records {
+                // A CSV cell can't hold a list
+                let lines = match &record.lines {
+                    Some(RecordLines::List(lines)) => {
+                        Some(RecordLines::Text(serde_json::to_string(lines)?))
+                    }
+                    lines => lines.clone(),
+                };
+                writer.serialize(QuoteRecord {
+                    lines,
+                    ..record.clone()
+                })?;
+            }
+            Ok(writer.into_inner().map_err(|why| why.to_string())?)
+        }
+        QuoteFileFormat::Json => Ok(serde_json::to_vec_pretty(records)?),
+    }
+}
+
+fn format_date(timestamp: f64) -> Option<RecordDate> {
+    DateTime::from_timestamp(timestamp as i64, 0).map(|date| RecordDate::Text(date.to_rfc3339()))
+}
+
+fn parse_date(value: Option<RecordDate>) -> Result<Option<f64>, String> {
+    let value = match value {
+        None => return Ok(None),
+        Some(RecordDate::Timestamp(timestamp)) => return Ok(Some(timestamp)),
+        Some(RecordDate::Text(value)) => value.trim().to_string(),
+    };
+    if value.is_empty() {
+        return Ok(None);
+    }
+
+    if let Ok(timestamp) = value.parse::<f64>() {
+        return Ok(Some(timestamp));
+    }
+    if let Ok(date) = DateTime::parse_from_rfc3339(&value) {
+        return Ok(Some(date.timestamp() as f64));
+    }
+    if let Some(date) = NaiveDate::parse_from_str(&value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    {
+        return Ok(Some(date.and_utc().timestamp() as f64));
+    }
+
+    Err(format!("couldn't read the date \"{}\"", value))
+}
+
+fn parse_lines(value: Option<RecordLines>) -> Result<Vec<QuoteLine>, String> {
+    let lines = match value {
+        None => return Ok(Vec::new()),
+        Some(RecordLines::List(lines)) => lines,
+        Some(RecordLines::Text(value)) if value.trim().is_empty() => return Ok(Vec::new()),
+        Some(RecordLines::Text(value)) => serde_json::from_str(&value)
+            .map_err(|why| format!("couldn't read the lines: {}", why))?,
+    };
+
+    let lines: Vec<QuoteLine> = lines
+        .into_iter()
+        .map(|line| QuoteLine {
+            speaker: line.speaker.trim().to_string(),
+            line: line.line.trim().to_string(),
+        })
+        .collect();
+    if lines
+        .iter()
+        .any(|line| line.speaker.is_empty() || line.line.is_empty())
+    {
+        return Err("every line needs a speaker and what they said".to_string());
+    }
+    if lines
+        .iter()
+        .any(|line| line.speaker.chars().count() > MAX_AUTHOR_LENGTH)
+    {
+        return Err(format!("speaker is over {} characters", MAX_AUTHOR_LENGTH));
+    }
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Option<RecordDate> {
+        Some(RecordDate::Text(value.to_string()))
+    }
+
+    #[test]
+    fn parse_date_reads_every_supported_format() {
+        assert_eq!(parse_date(None), Ok(None));
+        assert_eq!(
+            parse_date(Some(RecordDate::Timestamp(1700000000.0))),
+            Ok(Some(1700000000.0))
+        );
+        assert_eq!(parse_date(text(" 1700000000 ")), Ok(Some(1700000000.0)));
+        assert_eq!(
+            parse_date(text("2023-11-14T22:13:20+00:00")),
+            Ok(Some(1700000000.0))
+        );
+        assert_eq!(
+            parse_date(text("2023-11-15T00:13:20+02:00")),
+            Ok(Some(1700000000.0))
+        );
+        assert_eq!(parse_date(text("2023-11-14")), Ok(Some(1699920000.0)));
+    }
+
+    #[test]
+    fn parse_date_treats_blank_text_as_missing() {
+        assert_eq!(parse_date(text("   ")), Ok(None));
+    }
+
+    #[test]
+    fn parse_date_rejects_other_text() {
+        assert!(parse_date(text("yesterday")).is_err());
+        assert!(parse_date(text("14/11/2023")).is_err());
+    }
+
+    #[test]
+    fn format_date_round_trips() {
+        let formatted = format_date(1700000000.0);
+        assert_eq!(parse_date(formatted), Ok(Some(1700000000.0)));
+    }
+
+    #[test]
+    fn read_records_from_csv() {
+        let csv = "quote,author,said_at\nhello there, Obi-Wan ,2023-11-14\n\"a, b\",someone,\n";
+        let records = read_records(QuoteFileFormat::Csv, csv.as_bytes()).unwrap();
+        assert_eq!(records.len(), 2);
+
+        let first = records[0].as_ref().unwrap();
+        assert_eq!(first.quote, "hello there");
+        assert_eq!(first.author, "Obi-Wan");
+        assert!(matches!(&first.said_at, Some(RecordDate::Text(date)) if date == "2023-11-14"));
+
+        let second = records[1].as_ref().unwrap();
+        assert_eq!(second.quote, "a, b");
+        assert!(second.said_at.is_none());
+    }
+
+    #[test]
+    fn read_records_needs_quote_and_author_columns() {
+        let csv = "text,author\nhello,someone\n";
+        assert!(read_records(QuoteFileFormat::Csv, csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn read_records_from_json_reports_bad_rows_separately() {
+        let json = r#"[
+            {"quote": "hello", "author": "someone", "said_at": 1700000000},
+            {"quote": 5, "author": "someone"},
+            {"author": "nobody"}
+        ]"#;
+        let records = read_records(QuoteFileFormat::Json, json.as_bytes()).unwrap();
+        assert_eq!(records.len(), 3);
+        assert!(matches!(
+            records[0].as_ref().unwrap().said_at,
+            Some(RecordDate::Timestamp(_))
+        ));
+        assert!(records[1].is_err());
+        assert_eq!(records[2].as_ref().unwrap().quote, "");
+    }
+
+    #[test]
+    fn read_records_rejects_json_that_isnt_a_list() {
+        let json = r#"{"quote": "hello", "author": "someone"}"#;
+        assert!(read_records(QuoteFileFormat::Json, json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn conversation_lines_round_trip() {
+        let lines = vec![
+            QuoteLine {
+                speaker: "Alice".to_string(),
+                line: "did you hear that, \"Bob\"?".to_string(),
+            },
+            QuoteLine {
+                speaker: "Bob".to_string(),
+                line: "hear what?".to_string(),
+            },
+        ];
+        let record = QuoteRecord {
+            quote: "Alice: did you hear that?\nBob: hear what?".to_string(),
+            author: "Alice, Bob".to_string(),
+            lines: Some(RecordLines::List(lines.clone())),
+            ..Default::default()
+        };
+
+        for format in [QuoteFileFormat::Csv, QuoteFileFormat::Json] {
+            let contents = write_records(format, std::slice::from_ref(&record)).unwrap();
+            let records = read_records(format, &contents).unwrap();
+            let quote = records
+                .into_iter()
+                .next()
+                .unwrap()
+                .unwrap()
+                .into_new_quote(1, 2)
+                .unwrap();
+            assert_eq!(quote.lines, lines);
+        }
+    }
+}
@@ -1,85 +1,1713 @@
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
-use crate::data::db;
+use crate::constants::{get_quote_duplicate_similarity, get_quote_min_daily_score};
+use crate::data::markov::MarkovChain;
+use crate::data::{db, render};
 
+use chrono::Utc;
 use poise::serenity_prelude as serenity;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use tokio::sync::Mutex;
 
 use super::db::BotDatabase;
 
+/// Discord users the author filter refers to: the user bound to `?3`, and whoever the name
+/// bound to `?2` is an alias of or was saved from the messages of, within the guild bound to
+/// `?1`.
+const AUTHOR_USERS: &str = "(
+    SELECT ?3
+    UNION SELECT user_id FROM quote_aliases WHERE guild_id = ?1 AND alias = ?2
+    UNION SELECT author_user_id FROM quotes WHERE guild_id = ?1 AND author = ?2 COLLATE NOCASE
+)";
+
+/// Matches quotes by the author named `?2` or the Discord user `?3` within the guild bound to
+/// `?1`. Names are matched case-insensitively, including the speakers in conversations, and
+/// every name linked to the same user matches.
+fn author_matches() -> String {
+    format!(
+        "(author = ?2 COLLATE NOCASE
+        OR quotes.id IN (SELECT quote_id FROM quote_lines WHERE speaker = ?2 COLLATE NOCASE)
+        OR author_user_id IN {users}
+        OR author COLLATE NOCASE IN (
+            SELECT alias FROM quote_aliases WHERE guild_id = ?1 AND user_id IN {users}
+        )
+        OR quotes.id IN (
+            SELECT quote_id FROM quote_lines WHERE speaker COLLATE NOCASE IN (
+                SELECT alias FROM quote_aliases WHERE guild_id = ?1 AND user_id IN {users}
+            )
+        ))",
+        users = AUTHOR_USERS
+    )
+}
+
+// Most results a quote search returns
+const MAX_SEARCH_RESULTS: i64 = 50;
+// Longest name before a colon that's read as a speaker rather than part of the line
+const MAX_SPEAKER_LENGTH: usize = 32;
+// Longest fake quote that gets generated, in words
+const MAX_FAKE_QUOTE_WORDS: usize = 40;
+
+/// Custom ID prefix of the rating buttons on quote embeds, followed by `<quote id>:<vote>`.
+pub const VOTE_BUTTON_PREFIX: &str = "quote_vote:";
+
+/// Counts the thumbs up and down on the quote bound to the first parameter.
+const COUNT_VOTES: &str = "SELECT
+    COALESCE(SUM(vote = 1), 0) AS up, COALESCE(SUM(vote = -1), 0) AS down
+    FROM quote_votes WHERE quote_id = ?";
+
+/// The daily quote cycle the guild bound to `?1` is in.
+const CURRENT_CYCLE: &str =
+    "(SELECT COALESCE(MAX(cycle), 0) FROM daily_quotes WHERE guild_id = ?1)";
+
+/// Copies the quote bound to `?4` in the guild bound to `?5` into the audit table before it's
+/// changed, recording the action `?1` by user `?2` at time `?3`. Conversation lines are kept
+/// as a JSON array.
+const RECORD_AUDIT: &str = "INSERT INTO quote_audit
+    (quote_id, action, changed_by, changed_at, old_quote, old_author, old_author_user_id,
+    old_timestamp, old_source_link, old_submitted_by, old_created_at, old_guild_id, old_lines)
+    SELECT id, ?1, ?2, ?3, quote, author, author_user_id, timestamp, source_link,
+    submitted_by, created_at, guild_id,
+    (SELECT json_group_array(json_object('speaker', speaker, 'line', line))
+        FROM (SELECT speaker, line FROM quote_lines WHERE quote_id = quotes.id ORDER BY position)
+        HAVING COUNT(*) > 0)
+    FROM quotes WHERE id = ?4 AND guild_id = ?5";
+
 #[derive(FromRow, Debug, Clone)]
 pub struct QuoteMessage {
     pub id: i32,
     pub quote: String,
     pub author: String,
+    pub author_user_id: Option<i64>,
+    pub timestamp: Option<f64>,
+    pub source_link: Option<String>,
+    pub submitted_by: Option<i64>,
+    pub created_at: Option<f64>,
+    pub guild_id: Option<i64>,
+    /// The lines of a conversation, empty for quotes with a single author.
+    #[sqlx(skip)]
+    pub lines: Vec<QuoteLine>,
+    /// The Discord user who said the quote, either saved with it or linked by an alias.
+    #[sqlx(skip)]
+    pub linked_user_id: Option<i64>,
+}
+
+/// Who to find quotes by.
+#[derive(Debug, Clone)]
+pub enum QuoteAuthor {
+    /// An author's name, which may be an alias of a Discord user.
+    Name(String),
+    /// A Discord user, under any of their aliases.
+    User(serenity::UserId),
+}
+
+impl QuoteAuthor {
+    fn name(&self) -> Option<&str> {
+        match self {
+            QuoteAuthor::Name(name) => Some(name.trim()),
+            QuoteAuthor::User(_) => None,
+        }
+    }
+
+    fn user_id(&self) -> Option<i64> {
+        match self {
+            QuoteAuthor::Name(_) => None,
+            QuoteAuthor::User(user_id) => Some(user_id.get() as i64),
+        }
+    }
+}
+
+/// A name linked to a Discord user.
+#[derive(FromRow, Debug, Clone)]
+pub struct QuoteAlias {
+    pub alias: String,
+    pub user_id: i64,
+}
+
+/// One line of a conversation quote.
+#[derive(FromRow, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteLine {
+    pub speaker: String,
+    pub line: String,
+}
+
+/// A quote to be stored, along with where it came from if it was saved from a message.
+#[derive(Debug, Clone, Default)]
+pub struct NewQuote {
+    pub quote: String,
+    pub author: String,
+    pub author_user_id: Option<i64>,
+    pub timestamp: Option<f64>,
+    pub source_link: Option<String>,
+    pub submitted_by: i64,
+    pub created_at: Option<f64>,
+    pub guild_id: i64,
+    /// The lines of a conversation, empty for quotes with a single author.
+    pub lines: Vec<QuoteLine>,
+}
+
+impl NewQuote {
+    /// Make a quote out of a conversation. The quote text is the whole dialogue, and the
+    /// author lists everyone who spoke.
+    pub fn conversation(lines: Vec<QuoteLine>, submitted_by: i64, guild_id: i64) -> NewQuote {
+        let mut speakers: Vec<&str> = Vec::new();
+        for line in &lines {
+            if !speakers
+                .iter()
+                .any(|speaker| speaker.eq_ignore_ascii_case(&line.speaker))
+            {
+                speakers.push(&line.speaker);
+            }
+        }
+
+        NewQuote {
+            quote: lines
+                .iter()
+                .map(|line| format!("{}: {}", line.speaker, line.line))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            author: speakers.join(", "),
+            submitted_by,
+            guild_id,
+            lines,
+            ..Default::default()
+        }
+    }
+}
+
+/// A quote matching a search, with the matching words highlighted.
+#[derive(FromRow, Debug, Clone)]
+pub struct QuoteSearchResult {
+    pub id: i32,
+    pub author: String,
+    pub snippet: String,
+}
+
+/// Outcome of trying to store a quote.
+#[derive(Debug, Clone)]
+pub enum AddQuote {
+    /// The quote was stored.
+    Added(QuoteMessage),
+    /// Nothing was stored because this quote is already stored with the same or very similar
+    /// text.
+    Duplicate(QuoteMessage),
+}
+
+/// A quote posted as the daily quote. The quote is missing if it's since been deleted.
+#[derive(FromRow, Debug, Clone)]
+pub struct DailyQuotePost {
+    pub quote_id: i32,
+    pub posted_at: f64,
+    pub quote: Option<String>,
+    pub author: Option<String>,
+}
+
+/// Statistics about the quotes in a guild.
+#[derive(Debug, Clone, Default)]
+pub struct QuoteStats {
+    pub total: i64,
+    /// The most quoted people, most quotes first.
+    pub people: Vec<QuotedPerson>,
+    /// The users who added the most quotes and how many they added, most first.
+    pub submitters: Vec<(i64, i64)>,
+    /// The number of quotes added in each of the most recent months, as `YYYY-MM`, oldest first.
+    pub months: Vec<(String, i64)>,
+}
+
+/// Someone who's been quoted, counting every name linked to the same Discord user together.
+/// Lines they said in conversations count toward their longest and shortest quote.
+#[derive(FromRow, Debug, Clone)]
+pub struct QuotedPerson {
+    pub name: String,
+    pub user_id: Option<i64>,
+    pub quotes: i64,
+    pub longest_id: i32,
+    pub longest: String,
+    pub shortest_id: i32,
+    pub shortest: String,
+}
+
+/// A round of the quote guessing game: a quote and the authors to choose between.
+#[derive(Debug, Clone)]
+pub struct GameRound {
+    pub quote: QuoteMessage,
+    /// The real author and the decoys, in a random order.
+    pub choices: Vec<String>,
+    /// Index of the real author in `choices`.
+    pub answer: usize,
+}
+
+/// A player's running total in the quote guessing game.
+#[derive(FromRow, Debug, Clone)]
+pub struct GameScore {
+    pub user_id: i64,
+    pub points: i64,
+    pub guesses: i64,
+}
+
+/// What a bulk import did, row by row.
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Number of quotes stored.
+    pub imported: usize,
+    /// Rows that were skipped because they're already stored or repeat an earlier row.
+    pub duplicates: Vec<(usize, ImportDuplicate)>,
+    /// Rows that couldn't be read, with the reason why.
+    pub errors: Vec<(usize, String)>,
+}
+
+/// What an imported row turned out to be a duplicate of.
+#[derive(Debug, Clone, Copy)]
+pub enum ImportDuplicate {
+    /// A quote that's already stored, by ID.
+    Stored(i32),
+    /// An earlier row in the same import.
+    Row(usize),
+}
+
+/// A quote along with its net rating.
+#[derive(FromRow, Debug, Clone)]
+pub struct RatedQuote {
+    #[sqlx(flatten)]
+    pub quote: QuoteMessage,
+    pub score: i64,
+}
+
+/// Thumbs up and thumbs down counts for a quote.
+#[derive(FromRow, Debug, Clone, Default)]
+pub struct QuoteVotes {
+    pub up: i64,
+    pub down: i64,
+}
+
+impl QuoteVotes {
+    /// Create the rating buttons for a quote, labelled with the current counts.
+    pub fn create_buttons(&self, quote_id: i32) -> serenity::CreateActionRow {
+        serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(format!("{}{}:1", VOTE_BUTTON_PREFIX, quote_id))
+                .emoji('👍')
+                .label(self.up.to_string())
+                .style(serenity::ButtonStyle::Secondary),
+            serenity::CreateButton::new(format!("{}{}:-1", VOTE_BUTTON_PREFIX, quote_id))
+                .emoji('👎')
+                .label(self.down.to_string())
+                .style(serenity::ButtonStyle::Secondary),
+        ])
+    }
 }
 
 impl QuoteMessage {
-    /// Create an embed for this message.
-    pub fn create_embed(&self) -> serenity::CreateEmbed {
+    /// Create an embed for this message, with the avatar of whoever said it if they're linked
+    /// to a Discord user.
+    pub async fn create_embed(&self, ctx: &serenity::Context) -> serenity::CreateEmbed {
+        // Conversations are laid out line by line with each speaker in bold
+        let (title, description) = match self.lines.is_empty() {
+            true => (
+                format!("Quote by {}", self.author),
+                format!("## {}", self.quote),
+            ),
+            false => (
+                format!("Conversation between {}", self.author),
+                self.lines
+                    .iter()
+                    .map(|line| format!("**{}:** {}", line.speaker, line.line))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        };
+
         // Initialize the embed with the title and timestamp
-        serenity::CreateEmbed::default()
-            .title(format!("Quote by {}", self.author))
+        let mut embed = serenity::CreateEmbed::default()
+            .title(title)
             .color(serenity::Colour::GOLD)
-            .description(format!("## {}", self.quote))
-            .footer(serenity::CreateEmbedFooter::new(format!("{}", self.id)))
+            .description(description)
+            .footer(serenity::CreateEmbedFooter::new(format!("{}", self.id)));
+
+        if let Some(user_id) = self.linked_user_id {
+            embed = embed.field("Said by", format!("<@{}>", user_id), true);
+
+            let guild_id = self
+                .guild_id
+                .map(|guild_id| serenity::GuildId::new(guild_id as u64));
+            let user_id = serenity::UserId::new(user_id as u64);
+            if let Some((_, avatar)) = render::resolve_author(ctx, guild_id, user_id).await {
+                embed = embed.thumbnail(avatar);
+            }
+        }
+
+        match (self.submitted_by, self.created_at) {
+            (Some(user_id), Some(created_at)) => {
+                embed = embed.field(
+                    "Added by",
+                    format!("<@{}> on <t:{}:d>", user_id, created_at as i64),
+                    true,
+                );
+            }
+            (Some(user_id), None) => {
+                embed = embed.field("Added by", format!("<@{}>", user_id), true);
+            }
+            _ => {}
+        }
+
+        if let Some(link) = &self.source_link {
+            embed = embed
+                .url(link)
+                .field("Source", format!("[Jump to message]({})", link), true);
+        }
+
+        // Prefer when the quote was said over when it was added
+        if let Some(timestamp) = self
+            .timestamp
+            .or(self.created_at)
+            .and_then(|ts| serenity::Timestamp::from_unix_timestamp(ts as i64).ok())
+        {
+            embed = embed.timestamp(timestamp);
+        }
+
+        embed
+    }
+}
+
+/// Markov chains trained on a guild's quotes, for generating fake ones.
+#[derive(Debug, Default)]
+struct FakeQuotes {
+    all: MarkovChain,
+    /// A chain for each person, keyed by their Discord user if they're linked to one or their
+    /// lowercased name otherwise.
+    people: HashMap<String, MarkovChain>,
+    /// How many times each lowercased name has been trained under each person, since people
+    /// can share a name.
+    names: HashMap<String, HashMap<String, usize>>,
+}
+
+impl FakeQuotes {
+    /// Train the chains on a quote, or on each line of a conversation under its speaker.
+    fn train(&mut self, quote: &QuoteMessage, aliases: &HashMap<String, i64>) {
+        let said: Vec<(&str, &str, Option<i64>)> = match quote.lines.is_empty() {
+            true => vec![(&quote.author, &quote.quote, quote.author_user_id)],
+            false => quote
+                .lines
+                .iter()
+                .map(|line| (line.speaker.as_str(), line.line.as_str(), None))
+                .collect(),
+        };
+
+        for (name, text, user_id) in said {
+            let name = name.trim().to_lowercase();
+            let person = match user_id.or_else(|| aliases.get(&name).copied()) {
+                Some(user_id) => format!("<@{}>", user_id),
+                None => name.clone(),
+            };
+
+            self.all.train(text);
+            self.people.entry(person.clone()).or_default().train(text);
+            *self
+                .names
+                .entry(name)
+                .or_default()
+                .entry(person)
+                .or_default() += 1;
+        }
+    }
+
+    /// The chain for whoever a name belongs to, going by who's been quoted under it most.
+    fn person(&self, name: &str) -> Option<&MarkovChain> {
+        let (person, _) = self
+            .names
+            .get(&name.trim().to_lowercase())?
+            .iter()
+            .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)))?;
+        self.people.get(person)
     }
 }
 
 pub struct Quotes {
     db: Arc<Mutex<BotDatabase>>,
+    /// Fake quote chains for each guild, built the first time they're needed and then kept up
+    /// to date as quotes are added. Other changes drop them to be rebuilt.
+    fakes: HashMap<i64, FakeQuotes>,
 }
 
 impl Quotes {
     pub fn new(db: Arc<Mutex<db::BotDatabase>>) -> Quotes {
-        Quotes { db }
+        Quotes {
+            db,
+            fakes: HashMap::new(),
+        }
     }
 
-    /// Return a random quote from the db.
+    /// Return a random quote from the db for the given guild, if there are any.
     pub async fn get_random_quote(
         &self,
-        author: Option<String>,
-    ) -> Result<QuoteMessage, sqlx::Error> {
+        guild_id: serenity::GuildId,
+        author: Option<QuoteAuthor>,
+    ) -> Result<Option<QuoteMessage>, sqlx::Error> {
         let db_lock = self.db.lock().await;
-        let conn = db_lock.get_conn();
+        let mut conn = db_lock.get_conn().acquire().await?;
 
-        let query = match author {
-            Some(author) => format!(
-                "SELECT * FROM quotes WHERE author = '{}' ORDER BY RANDOM() LIMIT 1",
-                author
-            ),
-            None => "SELECT * FROM quotes ORDER BY RANDOM() LIMIT 1".to_string(),
+        let quote = match author {
+            Some(author) => {
+                let query = format!(
+                    "SELECT * FROM quotes WHERE guild_id = ?1 AND {} ORDER BY RANDOM() LIMIT 1",
+                    author_matches()
+                );
+                sqlx::query_as::<_, QuoteMessage>(&query)
+                    .bind(guild_id.get() as i64)
+                    .bind(author.name())
+                    .bind(author.user_id())
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
+            None => {
+                let query = "SELECT * FROM quotes WHERE guild_id = ? ORDER BY RANDOM() LIMIT 1";
+                sqlx::query_as::<_, QuoteMessage>(query)
+                    .bind(guild_id.get() as i64)
+                    .fetch_optional(&mut *conn)
+                    .await?
+            }
         };
 
-        let quote = sqlx::query_as::<_, QuoteMessage>(&query)
-            .fetch_one(conn)
+        match quote {
+            Some(quote) => Ok(Some(with_details(&mut conn, quote).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Pick the daily quote for a guild. Every quote comes up once per cycle, in a new random
+    /// order each cycle. Heavily downvoted quotes are skipped, and the better rated a quote is
+    /// the earlier in the cycle it's likely to come up.
+    pub async fn get_daily_quote(
+        &self,
+        guild_id: serenity::GuildId,
+    ) -> Result<Option<QuoteMessage>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let query = "SELECT quotes.*, COALESCE(SUM(quote_votes.vote), 0) AS score
+            FROM quotes LEFT JOIN quote_votes ON quote_votes.quote_id = quotes.id
+            WHERE quotes.guild_id = ?
+            GROUP BY quotes.id
+            HAVING score >= ?";
+        let candidates = sqlx::query_as::<_, RatedQuote>(query)
+            .bind(guild_id.get() as i64)
+            .bind(get_quote_min_daily_score())
+            .fetch_all(&mut *conn)
             .await?;
 
-        Ok(quote)
+        let query = format!(
+            "SELECT quote_id FROM daily_quotes WHERE guild_id = ?1 AND cycle = {}",
+            CURRENT_CYCLE
+        );
+        let posted: HashSet<i32> = sqlx::query_as::<_, (i32,)>(&query)
+            .bind(guild_id.get() as i64)
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|(quote_id,)| quote_id)
+            .collect();
+
+        // Start a new cycle once everything has been posted
+        let mut remaining = candidates
+            .iter()
+            .filter(|rated| !posted.contains(&rated.quote.id))
+            .collect::<Vec<_>>();
+        if remaining.is_empty() {
+            remaining = candidates.iter().collect();
+        }
+
+        // Unrated and downvoted quotes keep a base chance of being picked
+        let quote = remaining
+            .choose_weighted(&mut thread_rng(), |rated| 1 + rated.score.max(0))
+            .ok()
+            .map(|rated| rated.quote.clone());
+
+        match quote {
+            Some(quote) => Ok(Some(with_details(&mut conn, quote).await?)),
+            None => Ok(None),
+        }
     }
 
-    pub async fn add_quote(
+    /// Record that a quote was posted as the daily quote, starting a new cycle if it was
+    /// already posted in the current one.
+    pub async fn record_daily_quote(
         &self,
-        quote: String,
-        author: String,
-    ) -> Result<QuoteMessage, sqlx::Error> {
+        guild_id: serenity::GuildId,
+        quote_id: i32,
+    ) -> Result<(), sqlx::Error> {
         let db_lock = self.db.lock().await;
         let conn = db_lock.get_conn();
 
-        let query = "INSERT INTO quotes (quote, author) VALUES (?, ?)";
-        sqlx::query(query)
-            .bind(quote.clone())
-            .bind(author.clone())
+        let query = format!(
+            "INSERT INTO daily_quotes (guild_id, quote_id, cycle, posted_at)
+            SELECT ?1, ?2, cycle + EXISTS (
+                SELECT 1 FROM daily_quotes WHERE guild_id = ?1 AND quote_id = ?2 AND cycle = current.cycle
+            ), ?3
+            FROM (SELECT {} AS cycle) AS current",
+            CURRENT_CYCLE
+        );
+        sqlx::query(&query)
+            .bind(guild_id.get() as i64)
+            .bind(quote_id)
+            .bind(Utc::now().timestamp() as f64)
             .execute(conn)
             .await?;
 
-        // readback the stored quote
-        let query = "SELECT * FROM quotes ORDER BY id DESC LIMIT 1";
-        let quote = sqlx::query_as::<_, QuoteMessage>(query)
+        Ok(())
+    }
+
+    /// Return the most recent daily quotes posted in a guild, newest first.
+    pub async fn get_daily_history(
+        &self,
+        guild_id: serenity::GuildId,
+        limit: i64,
+    ) -> Result<Vec<DailyQuotePost>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let query =
+            "SELECT daily_quotes.quote_id, daily_quotes.posted_at, quotes.quote, quotes.author
+            FROM daily_quotes LEFT JOIN quotes ON quotes.id = daily_quotes.quote_id
+            WHERE daily_quotes.guild_id = ?
+            ORDER BY daily_quotes.id DESC
+            LIMIT ?";
+        sqlx::query_as::<_, DailyQuotePost>(query)
+            .bind(guild_id.get() as i64)
+            .bind(limit)
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Return the best rated quotes in a guild, optionally filtered by author.
+    pub async fn get_top_quotes(
+        &self,
+        guild_id: serenity::GuildId,
+        author: Option<QuoteAuthor>,
+        limit: i64,
+    ) -> Result<Vec<RatedQuote>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let query = format!(
+            "SELECT quotes.*, SUM(quote_votes.vote) AS score
+            FROM quotes JOIN quote_votes ON quote_votes.quote_id = quotes.id
+            WHERE quotes.guild_id = ?1 AND (?2 IS NULL AND ?3 IS NULL OR {})
+            GROUP BY quotes.id
+            HAVING score > 0
+            ORDER BY score DESC, quotes.id
+            LIMIT ?4",
+            author_matches()
+        );
+
+        sqlx::query_as::<_, RatedQuote>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(author.as_ref().and_then(QuoteAuthor::name))
+            .bind(author.as_ref().and_then(QuoteAuthor::user_id))
+            .bind(limit)
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Return the thumbs up and down counts for a quote.
+    pub async fn get_votes(&self, quote_id: i32) -> Result<QuoteVotes, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        sqlx::query_as::<_, QuoteVotes>(COUNT_VOTES)
+            .bind(quote_id)
             .fetch_one(conn)
+            .await
+    }
+
+    /// Record a user's vote on a quote, replacing any earlier vote of theirs. Voting the same
+    /// way twice takes the vote back. Returns the new counts, or `None` if the quote doesn't
+    /// exist.
+    pub async fn vote(
+        &self,
+        quote_id: i32,
+        user_id: serenity::UserId,
+        vote: i64,
+    ) -> Result<Option<QuoteVotes>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut tx = db_lock.get_conn().begin().await?;
+
+        let exists: Option<(i32,)> = sqlx::query_as("SELECT id FROM quotes WHERE id = ?")
+            .bind(quote_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if exists.is_none() {
+            return Ok(None);
+        }
+
+        let previous: Option<(i64,)> =
+            sqlx::query_as("SELECT vote FROM quote_votes WHERE quote_id = ? AND user_id = ?")
+                .bind(quote_id)
+                .bind(user_id.get() as i64)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let query = match previous {
+            Some((previous,)) if previous == vote => {
+                "DELETE FROM quote_votes WHERE quote_id = ?1 AND user_id = ?2"
+            }
+            _ => {
+                "INSERT INTO quote_votes (quote_id, user_id, vote) VALUES (?1, ?2, ?3)
+                ON CONFLICT (quote_id, user_id) DO UPDATE SET vote = excluded.vote"
+            }
+        };
+        sqlx::query(query)
+            .bind(quote_id)
+            .bind(user_id.get() as i64)
+            .bind(vote)
+            .execute(&mut *tx)
+            .await?;
+
+        let votes = sqlx::query_as::<_, QuoteVotes>(COUNT_VOTES)
+            .bind(quote_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(votes))
+    }
+
+    /// Return the quote with the given ID, if it exists in the guild.
+    pub async fn get_quote(
+        &self,
+        guild_id: serenity::GuildId,
+        id: i32,
+    ) -> Result<Option<QuoteMessage>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let quote =
+            sqlx::query_as::<_, QuoteMessage>("SELECT * FROM quotes WHERE id = ? AND guild_id = ?")
+                .bind(id)
+                .bind(guild_id.get() as i64)
+                .fetch_optional(&mut *conn)
+                .await?;
+
+        match quote {
+            Some(quote) => Ok(Some(with_details(&mut conn, quote).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Change the text and/or author of a quote, recording the previous values in the audit
+    /// table. Returns the updated quote, or `None` if it doesn't exist in the guild.
+    pub async fn edit_quote(
+        &mut self,
+        guild_id: serenity::GuildId,
+        id: i32,
+        quote: Option<String>,
+        author: Option<String>,
+        changed_by: serenity::UserId,
+    ) -> Result<Option<QuoteMessage>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut tx = db_lock.get_conn().begin().await?;
+
+        let audited = sqlx::query(RECORD_AUDIT)
+            .bind("edit")
+            .bind(changed_by.get() as i64)
+            .bind(Utc::now().timestamp() as f64)
+            .bind(id)
+            .bind(guild_id.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        if audited.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        sqlx::query(
+            "UPDATE quotes SET quote = COALESCE(?, quote), author = COALESCE(?, author)
+            WHERE id = ? AND guild_id = ?",
+        )
+        .bind(quote)
+        .bind(author)
+        .bind(id)
+        .bind(guild_id.get() as i64)
+        .execute(&mut *tx)
+        .await?;
+
+        let updated = sqlx::query_as::<_, QuoteMessage>("SELECT * FROM quotes WHERE id = ?")
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let updated = with_details(&mut tx, updated).await?;
+
+        tx.commit().await?;
+        self.fakes.remove(&(guild_id.get() as i64));
+
+        Ok(Some(updated))
+    }
+
+    /// Delete a quote and its lines, keeping a copy of them in the audit table. Returns whether
+    /// it existed in the guild.
+    pub async fn delete_quote(
+        &mut self,
+        guild_id: serenity::GuildId,
+        id: i32,
+        changed_by: serenity::UserId,
+    ) -> Result<bool, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut tx = db_lock.get_conn().begin().await?;
+
+        let audited = sqlx::query(RECORD_AUDIT)
+            .bind("delete")
+            .bind(changed_by.get() as i64)
+            .bind(Utc::now().timestamp() as f64)
+            .bind(id)
+            .bind(guild_id.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        if audited.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM quotes WHERE id = ? AND guild_id = ?")
+            .bind(id)
+            .bind(guild_id.get() as i64)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM quote_lines WHERE quote_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        self.fakes.remove(&(guild_id.get() as i64));
+
+        Ok(true)
+    }
+
+    /// Search the quotes in a guild for the given words, optionally filtered by author.
+    /// Results are ordered by relevance.
+    pub async fn search_quotes(
+        &self,
+        guild_id: serenity::GuildId,
+        text: &str,
+        author: Option<QuoteAuthor>,
+    ) -> Result<Vec<QuoteSearchResult>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let terms = search_terms(text);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let query = format!(
+            "SELECT quotes.id, quotes.author,
+                snippet(quotes_fts, 0, '**', '**', '…', 16) AS snippet
+            FROM quotes_fts JOIN quotes ON quotes.id = quotes_fts.rowid
+            WHERE quotes.guild_id = ?1 AND quotes_fts MATCH ?4 AND (?2 IS NULL AND ?3 IS NULL OR {})
+            ORDER BY rank LIMIT ?5",
+            author_matches()
+        );
+
+        sqlx::query_as::<_, QuoteSearchResult>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(author.as_ref().and_then(QuoteAuthor::name))
+            .bind(author.as_ref().and_then(QuoteAuthor::user_id))
+            .bind(terms)
+            .bind(MAX_SEARCH_RESULTS)
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Gather statistics about the quotes in a guild, with up to `limit` entries in each
+    /// leaderboard and the last `months` months of additions.
+    pub async fn get_stats(
+        &self,
+        guild_id: serenity::GuildId,
+        limit: i64,
+        months: i64,
+    ) -> Result<QuoteStats, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let (total,) =
+            sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM quotes WHERE guild_id = ?")
+                .bind(guild_id.get() as i64)
+                .fetch_one(&mut *conn)
+                .await?;
+
+        // Every quote is attributed to its author, or each line of a conversation to its
+        // speaker, and then to the user that name is linked to if there is one
+        let query = "WITH attributions AS (
+                SELECT id AS quote_id, quote AS text, author AS name, author_user_id AS user_id
+                FROM quotes
+                WHERE guild_id = ?1 AND id NOT IN (SELECT quote_id FROM quote_lines)
+                UNION ALL
+                SELECT quotes.id, quote_lines.line, quote_lines.speaker, NULL
+                FROM quote_lines JOIN quotes ON quotes.id = quote_lines.quote_id
+                WHERE quotes.guild_id = ?1
+            ),
+            people AS (
+                SELECT attributions.quote_id, attributions.text, attributions.name,
+                    COALESCE(attributions.user_id, quote_aliases.user_id) AS user_id
+                FROM attributions LEFT JOIN quote_aliases
+                    ON quote_aliases.guild_id = ?1 AND quote_aliases.alias = attributions.name
+            ),
+            ranked AS (
+                SELECT *,
+                    COALESCE(CAST(user_id AS TEXT), lower(name)) AS person,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY COALESCE(CAST(user_id AS TEXT), lower(name))
+                        ORDER BY length(text) DESC, quote_id
+                    ) AS longest_rank,
+                    ROW_NUMBER() OVER (
+                        PARTITION BY COALESCE(CAST(user_id AS TEXT), lower(name))
+                        ORDER BY length(text), quote_id
+                    ) AS shortest_rank
+                FROM people
+            )
+            SELECT MIN(name) AS name, user_id, COUNT(DISTINCT quote_id) AS quotes,
+                MAX(CASE WHEN longest_rank = 1 THEN quote_id END) AS longest_id,
+                MAX(CASE WHEN longest_rank = 1 THEN text END) AS longest,
+                MAX(CASE WHEN shortest_rank = 1 THEN quote_id END) AS shortest_id,
+                MAX(CASE WHEN shortest_rank = 1 THEN text END) AS shortest
+            FROM ranked
+            GROUP BY person
+            ORDER BY quotes DESC, name
+            LIMIT ?2";
+        let people = sqlx::query_as::<_, QuotedPerson>(query)
+            .bind(guild_id.get() as i64)
+            .bind(limit)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let query = "SELECT submitted_by, COUNT(*) AS quotes FROM quotes
+            WHERE guild_id = ? AND submitted_by IS NOT NULL
+            GROUP BY submitted_by
+            ORDER BY quotes DESC, submitted_by
+            LIMIT ?";
+        let submitters = sqlx::query_as::<_, (i64, i64)>(query)
+            .bind(guild_id.get() as i64)
+            .bind(limit)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let query = "SELECT strftime('%Y-%m', created_at, 'unixepoch') AS month, COUNT(*)
+            FROM quotes
+            WHERE guild_id = ? AND created_at IS NOT NULL
+            GROUP BY month
+            ORDER BY month DESC
+            LIMIT ?";
+        let mut added = sqlx::query_as::<_, (String, i64)>(query)
+            .bind(guild_id.get() as i64)
+            .bind(months)
+            .fetch_all(&mut *conn)
             .await?;
+        added.reverse();
+
+        Ok(QuoteStats {
+            total,
+            people,
+            submitters,
+            months: added,
+        })
+    }
+
+    /// Pick a quote for the guessing game along with up to `decoys` other people to guess
+    /// between. Conversations are left out since they name their speakers, and the decoys are
+    /// never another name for the real author. Returns `None` if there's nobody to be a decoy.
+    pub async fn get_game_round(
+        &self,
+        guild_id: serenity::GuildId,
+        decoys: i64,
+    ) -> Result<Option<GameRound>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let query = "SELECT * FROM quotes
+            WHERE guild_id = ? AND id NOT IN (SELECT quote_id FROM quote_lines)
+            ORDER BY RANDOM() LIMIT 1";
+        let Some(quote) = sqlx::query_as::<_, QuoteMessage>(query)
+            .bind(guild_id.get() as i64)
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let quote = with_details(&mut conn, quote).await?;
+
+        // The author filter is NULL rather than false for quotes it can't rule in, so that's
+        // treated as not matching. Each decoy is a different person, however many names they
+        // go by.
+        let query = format!(
+            "SELECT MIN(author) FROM quotes
+            WHERE guild_id = ?1 AND id NOT IN (SELECT quote_id FROM quote_lines)
+                AND NOT COALESCE({}, FALSE)
+            GROUP BY COALESCE(
+                CAST(author_user_id AS TEXT),
+                (SELECT CAST(user_id AS TEXT) FROM quote_aliases
+                    WHERE guild_id = ?1 AND alias = quotes.author),
+                lower(author)
+            )
+            ORDER BY RANDOM() LIMIT ?4",
+            author_matches()
+        );
+        let decoys = sqlx::query_as::<_, (String,)>(&query)
+            .bind(guild_id.get() as i64)
+            .bind(&quote.author)
+            .bind(quote.linked_user_id)
+            .bind(decoys)
+            .fetch_all(&mut *conn)
+            .await?;
+        if decoys.is_empty() {
+            return Ok(None);
+        }
+
+        let mut choices: Vec<String> = decoys.into_iter().map(|(author,)| author).collect();
+        choices.push(quote.author.clone());
+        choices.shuffle(&mut thread_rng());
+        let answer = choices
+            .iter()
+            .position(|choice| *choice == quote.author)
+            .unwrap_or_default();
+
+        Ok(Some(GameRound {
+            quote,
+            choices,
+            answer,
+        }))
+    }
+
+    /// Add the guesses from a round of the guessing game to the players' totals, a point for
+    /// each right answer.
+    pub async fn record_game_guesses(
+        &self,
+        guild_id: serenity::GuildId,
+        guesses: &[(serenity::UserId, bool)],
+    ) -> Result<(), sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut tx = db_lock.get_conn().begin().await?;
+
+        for (user_id, correct) in guesses {
+            sqlx::query(
+                "INSERT INTO quote_game_scores (guild_id, user_id, points, guesses)
+                VALUES (?, ?, ?, 1)
+                ON CONFLICT (guild_id, user_id) DO UPDATE
+                SET points = points + excluded.points, guesses = guesses + 1",
+            )
+            .bind(guild_id.get() as i64)
+            .bind(user_id.get() as i64)
+            .bind(*correct as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await
+    }
+
+    /// Return the players with the most points in the guessing game, best first.
+    pub async fn get_game_leaderboard(
+        &self,
+        guild_id: serenity::GuildId,
+        limit: i64,
+    ) -> Result<Vec<GameScore>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        sqlx::query_as::<_, GameScore>(
+            "SELECT user_id, points, guesses FROM quote_game_scores
+            WHERE guild_id = ? AND points > 0
+            ORDER BY points DESC, guesses, user_id
+            LIMIT ?",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(limit)
+        .fetch_all(conn)
+        .await
+    }
+
+    /// Generate a fake quote from the quotes in a guild, or from only those by one author.
+    /// Returns `None` if there are no quotes to learn from.
+    pub async fn get_fake_quote(
+        &mut self,
+        guild_id: serenity::GuildId,
+        author: Option<&str>,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let guild_id = guild_id.get() as i64;
+        if !self.fakes.contains_key(&guild_id) {
+            let fakes = self.train_fake_quotes(guild_id).await?;
+            self.fakes.insert(guild_id, fakes);
+        }
+
+        let fakes = &self.fakes[&guild_id];
+        let chain = match author {
+            Some(author) => match fakes.person(author) {
+                Some(chain) => chain,
+                None => return Ok(None),
+            },
+            None => &fakes.all,
+        };
+
+        Ok(chain.generate(&mut thread_rng(), MAX_FAKE_QUOTE_WORDS))
+    }
+
+    /// Train fake quote chains on every quote in a guild.
+    async fn train_fake_quotes(&self, guild_id: i64) -> Result<FakeQuotes, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let aliases = get_alias_map(&mut conn, guild_id).await?;
+        let quotes = sqlx::query_as::<_, QuoteMessage>("SELECT * FROM quotes WHERE guild_id = ?")
+            .bind(guild_id)
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut lines = get_guild_lines(&mut conn, guild_id).await?;
+
+        let mut fakes = FakeQuotes::default();
+        for mut quote in quotes {
+            quote.lines = lines.remove(&quote.id).unwrap_or_default();
+            fakes.train(&quote, &aliases);
+        }
+
+        Ok(fakes)
+    }
+
+    /// Return up to 25 distinct authors in the guild starting with the given text.
+    pub async fn get_authors(
+        &self,
+        guild_id: serenity::GuildId,
+        partial: &str,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        // Match the text literally, LIKE treats % and _ as wildcards
+        let pattern = format!(
+            "{}%",
+            partial
+                .trim()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+
+        // Suggest the speakers in conversations rather than their combined author
+        let authors: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM (
+                SELECT author AS name FROM quotes
+                WHERE guild_id = ?1 AND id NOT IN (SELECT quote_id FROM quote_lines)
+                UNION ALL
+                SELECT quote_lines.speaker AS name
+                FROM quote_lines JOIN quotes ON quotes.id = quote_lines.quote_id
+                WHERE quotes.guild_id = ?1
+            )
+            WHERE name LIKE ?2 ESCAPE '\\'
+            GROUP BY name COLLATE NOCASE
+            ORDER BY COUNT(*) DESC, name COLLATE NOCASE
+            LIMIT 25",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(pattern)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(authors.into_iter().map(|(author,)| author).collect())
+    }
+
+    /// Link a name to a Discord user, replacing whoever it was linked to before.
+    pub async fn add_alias(
+        &mut self,
+        guild_id: serenity::GuildId,
+        alias: &str,
+        user_id: serenity::UserId,
+        added_by: serenity::UserId,
+    ) -> Result<(), sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        sqlx::query(
+            "INSERT INTO quote_aliases (guild_id, alias, user_id, added_by) VALUES (?, ?, ?, ?)
+            ON CONFLICT (guild_id, alias) DO UPDATE
+            SET user_id = excluded.user_id, added_by = excluded.added_by",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(alias.trim())
+        .bind(user_id.get() as i64)
+        .bind(added_by.get() as i64)
+        .execute(conn)
+        .await?;
+        self.fakes.remove(&(guild_id.get() as i64));
+
+        Ok(())
+    }
+
+    /// Unlink a name from whoever it's linked to. Returns the alias if there was one.
+    pub async fn remove_alias(
+        &mut self,
+        guild_id: serenity::GuildId,
+        alias: &str,
+    ) -> Result<Option<QuoteAlias>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let removed = sqlx::query_as::<_, QuoteAlias>(
+            "DELETE FROM quote_aliases WHERE guild_id = ? AND alias = ?
+            RETURNING alias, user_id",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(alias.trim())
+        .fetch_optional(conn)
+        .await?;
+        self.fakes.remove(&(guild_id.get() as i64));
+
+        Ok(removed)
+    }
+
+    /// Return the aliases in a guild, optionally only those of one user.
+    pub async fn get_aliases(
+        &self,
+        guild_id: serenity::GuildId,
+        user_id: Option<serenity::UserId>,
+    ) -> Result<Vec<QuoteAlias>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        sqlx::query_as::<_, QuoteAlias>(
+            "SELECT alias, user_id FROM quote_aliases
+            WHERE guild_id = ?1 AND (?2 IS NULL OR user_id = ?2)
+            ORDER BY user_id, alias",
+        )
+        .bind(guild_id.get() as i64)
+        .bind(user_id.map(|user_id| user_id.get() as i64))
+        .fetch_all(conn)
+        .await
+    }
+
+    /// Store a quote, unless the guild already has a quote with the same or very similar text.
+    pub async fn add_quote(&mut self, quote: NewQuote) -> Result<AddQuote, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let stored = fingerprint_quotes(&mut conn, quote.guild_id).await?;
+        let fingerprint = Fingerprint::new(&quote.quote);
+        if let Some(existing) = fingerprint.most_similar(&stored) {
+            let existing = with_details(&mut conn, existing.clone()).await?;
+            return Ok(AddQuote::Duplicate(existing));
+        }
+
+        let stored = insert_quote(&mut conn, quote).await?;
+        learn_fake_quotes(&mut self.fakes, &mut conn, &stored).await?;
+
+        Ok(AddQuote::Added(stored))
+    }
+
+    /// Store a quote without checking whether it's already stored.
+    pub async fn add_quote_unchecked(
+        &mut self,
+        quote: NewQuote,
+    ) -> Result<QuoteMessage, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let stored = insert_quote(&mut conn, quote).await?;
+        learn_fake_quotes(&mut self.fakes, &mut conn, &stored).await?;
+
+        Ok(stored)
+    }
+
+    /// Store a batch of quotes in a guild in one transaction, skipping any that are already
+    /// stored or repeat an earlier row. Rows are numbered from 1 in the report. If any row is
+    /// an error, nothing is stored so the file can be fixed and imported again.
+    pub async fn import_quotes(
+        &mut self,
+        guild_id: serenity::GuildId,
+        rows: Vec<Result<NewQuote, String>>,
+    ) -> Result<ImportReport, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut tx = db_lock.get_conn().begin().await?;
+
+        let stored = fingerprint_quotes(&mut tx, guild_id.get() as i64).await?;
+
+        let mut report = ImportReport::default();
+        let mut accepted: Vec<(Fingerprint, usize)> = Vec::new();
+        let mut to_insert = Vec::new();
+        for (row, quote) in rows.into_iter().enumerate() {
+            let row = row + 1;
+            let quote = match quote {
+                Ok(quote) => quote,
+                Err(why) => {
+                    report.errors.push((row, why));
+                    continue;
+                }
+            };
+
+            let fingerprint = Fingerprint::new(&quote.quote);
+            if let Some(existing) = fingerprint.most_similar(&stored) {
+                report
+                    .duplicates
+                    .push((row, ImportDuplicate::Stored(existing.id)));
+            } else if let Some(earlier) = fingerprint.most_similar(&accepted) {
+                report
+                    .duplicates
+                    .push((row, ImportDuplicate::Row(*earlier)));
+            } else {
+                accepted.push((fingerprint, row));
+                to_insert.push(quote);
+            }
+        }
+
+        if !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        for quote in to_insert {
+            insert_quote(&mut tx, quote).await?;
+            report.imported += 1;
+        }
+        tx.commit().await?;
+        self.fakes.remove(&(guild_id.get() as i64));
+
+        Ok(report)
+    }
+
+    /// Return every quote in a guild with its lines, oldest first.
+    pub async fn get_all_quotes(
+        &self,
+        guild_id: serenity::GuildId,
+    ) -> Result<Vec<QuoteMessage>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let mut conn = db_lock.get_conn().acquire().await?;
+
+        let mut quotes = sqlx::query_as::<_, QuoteMessage>(
+            "SELECT * FROM quotes WHERE guild_id = ? ORDER BY id",
+        )
+        .bind(guild_id.get() as i64)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let mut lines = get_guild_lines(&mut conn, guild_id.get() as i64).await?;
+        for quote in &mut quotes {
+            quote.lines = lines.remove(&quote.id).unwrap_or_default();
+        }
+
+        Ok(quotes)
+    }
+}
+
+/// The lines of every conversation quote in a guild, by quote ID.
+async fn get_guild_lines(
+    conn: &mut sqlx::SqliteConnection,
+    guild_id: i64,
+) -> Result<HashMap<i32, Vec<QuoteLine>>, sqlx::Error> {
+    let query = "SELECT quote_lines.quote_id, quote_lines.speaker, quote_lines.line
+        FROM quote_lines JOIN quotes ON quotes.id = quote_lines.quote_id
+        WHERE quotes.guild_id = ?
+        ORDER BY quote_lines.quote_id, quote_lines.position";
+    let mut lines: HashMap<i32, Vec<QuoteLine>> = HashMap::new();
+    for (quote_id, speaker, line) in sqlx::query_as::<_, (i32, String, String)>(query)
+        .bind(guild_id)
+        .fetch_all(&mut *conn)
+        .await?
+    {
+        lines
+            .entry(quote_id)
+            .or_default()
+            .push(QuoteLine { speaker, line });
+    }
+
+    Ok(lines)
+}
+
+/// Train a guild's fake quote chains on a newly stored quote, if they've been built.
+async fn learn_fake_quotes(
+    fakes: &mut HashMap<i64, FakeQuotes>,
+    conn: &mut sqlx::SqliteConnection,
+    quote: &QuoteMessage,
+) -> Result<(), sqlx::Error> {
+    let Some(guild_id) = quote.guild_id else {
+        return Ok(());
+    };
+    if let Some(guild_fakes) = fakes.get_mut(&guild_id) {
+        let aliases = get_alias_map(conn, guild_id).await?;
+        guild_fakes.train(quote, &aliases);
+    }
+
+    Ok(())
+}
+
+/// Return the user each alias in a guild is linked to, keyed by the lowercased alias.
+async fn get_alias_map(
+    conn: &mut sqlx::SqliteConnection,
+    guild_id: i64,
+) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let aliases = sqlx::query_as::<_, QuoteAlias>(
+        "SELECT alias, user_id FROM quote_aliases WHERE guild_id = ?",
+    )
+    .bind(guild_id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(aliases
+        .into_iter()
+        .map(|alias| (alias.alias.to_lowercase(), alias.user_id))
+        .collect())
+}
+
+async fn insert_quote(
+    conn: &mut sqlx::SqliteConnection,
+    quote: NewQuote,
+) -> Result<QuoteMessage, sqlx::Error> {
+    let query = "INSERT INTO quotes
+        (quote, author, author_user_id, timestamp, source_link, submitted_by, created_at, guild_id)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)";
+    let id = sqlx::query(query)
+        .bind(quote.quote)
+        .bind(quote.author)
+        .bind(quote.author_user_id)
+        .bind(quote.timestamp)
+        .bind(quote.source_link)
+        .bind(quote.submitted_by)
+        .bind(
+            quote
+                .created_at
+                .unwrap_or_else(|| Utc::now().timestamp() as f64),
+        )
+        .bind(quote.guild_id)
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+    for (position, line) in quote.lines.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO quote_lines (quote_id, position, speaker, line) VALUES (?, ?, ?, ?)",
+        )
+        .bind(id)
+        .bind(position as i64)
+        .bind(&line.speaker)
+        .bind(&line.line)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    // readback the stored quote
+    let stored = sqlx::query_as::<_, QuoteMessage>("SELECT * FROM quotes WHERE id = ?")
+        .bind(id)
+        .fetch_one(&mut *conn)
+        .await?;
+    with_details(conn, stored).await
+}
+
+/// Fill in the lines of a quote if it's a conversation, and who said it if they're linked to
+/// a Discord user.
+async fn with_details(
+    conn: &mut sqlx::SqliteConnection,
+    mut quote: QuoteMessage,
+) -> Result<QuoteMessage, sqlx::Error> {
+    quote.lines = sqlx::query_as::<_, QuoteLine>(
+        "SELECT speaker, line FROM quote_lines WHERE quote_id = ? ORDER BY position",
+    )
+    .bind(quote.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    quote.linked_user_id = match quote.author_user_id {
+        Some(user_id) => Some(user_id),
+        None if quote.lines.is_empty() => sqlx::query_as::<_, (i64,)>(
+            "SELECT user_id FROM quote_aliases WHERE guild_id = ? AND alias = ?",
+        )
+        .bind(quote.guild_id)
+        .bind(&quote.author)
+        .fetch_optional(&mut *conn)
+        .await?
+        .map(|(user_id,)| user_id),
+        None => None,
+    };
+
+    Ok(quote)
+}
+
+/// Read a conversation written one line per speaker as `Speaker: what they said`. Lines
+/// without a speaker continue the line before them. Returns `None` unless there are at least
+/// two lines and the first has a speaker.
+pub fn parse_conversation(text: &str) -> Option<Vec<QuoteLine>> {
+    let mut lines: Vec<QuoteLine> = Vec::new();
+
+    for raw in text.lines().map(str::trim).filter(|raw| !raw.is_empty()) {
+        let speaker_line = raw
+            .split_once(':')
+            .map(|(speaker, line)| (speaker.trim(), line.trim()))
+            .filter(|(speaker, line)| {
+                !speaker.is_empty()
+                    && speaker.chars().count() <= MAX_SPEAKER_LENGTH
+                    && !line.is_empty()
+            });
+
+        match (speaker_line, lines.last_mut()) {
+            (Some((speaker, line)), _) => lines.push(QuoteLine {
+                speaker: speaker.to_string(),
+                line: line.to_string(),
+            }),
+            (None, Some(previous)) => {
+                previous.line.push('\n');
+                previous.line.push_str(raw);
+            }
+            (None, None) => return None,
+        }
+    }
+
+    match lines.len() {
+        0 | 1 => None,
+        _ => Some(lines),
+    }
+}
+
+/// Load the quotes in a guild along with their fingerprints, for finding duplicates.
+async fn fingerprint_quotes(
+    conn: &mut sqlx::SqliteConnection,
+    guild_id: i64,
+) -> Result<Vec<(Fingerprint, QuoteMessage)>, sqlx::Error> {
+    let stored = sqlx::query_as::<_, QuoteMessage>("SELECT * FROM quotes WHERE guild_id = ?")
+        .bind(guild_id)
+        .fetch_all(conn)
+        .await?;
+
+    Ok(stored
+        .into_iter()
+        .map(|quote| (Fingerprint::new(&quote.quote), quote))
+        .collect())
+}
+
+/// Normalized text of a quote and the counts of its character bigrams, for comparing quotes.
+struct Fingerprint {
+    text: String,
+    bigrams: HashMap<(char, char), usize>,
+    total: usize,
+}
+
+impl Fingerprint {
+    fn new(quote: &str) -> Fingerprint {
+        let text = normalize_quote(quote);
+
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut bigrams = HashMap::new();
+        for pair in chars.windows(2) {
+            *bigrams.entry((pair[0], pair[1])).or_insert(0) += 1;
+        }
+
+        Fingerprint {
+            text,
+            bigrams,
+            total: chars.len().saturating_sub(1),
+        }
+    }
+
+    /// Similarity to another quote from 0 to 1, measured as the Sørensen–Dice coefficient of
+    /// their bigrams.
+    fn similarity(&self, other: &Fingerprint) -> f64 {
+        // Quotes of only emoji or punctuation have nothing left to compare
+        if self.text.is_empty() || other.text.is_empty() {
+            return 0.0;
+        }
+        if self.text == other.text {
+            return 1.0;
+        }
+        if self.total == 0 || other.total == 0 {
+            return 0.0;
+        }
+
+        let shared: usize = self
+            .bigrams
+            .iter()
+            .map(|(bigram, count)| {
+                other
+                    .bigrams
+                    .get(bigram)
+                    .map_or(0, |other| *count.min(other))
+            })
+            .sum();
+
+        2.0 * shared as f64 / (self.total + other.total) as f64
+    }
+
+    /// Return whichever candidate is most similar to this quote, if any are similar enough to
+    /// count as a duplicate.
+    fn most_similar<'a, T>(&self, candidates: &'a [(Fingerprint, T)]) -> Option<&'a T> {
+        let threshold = get_quote_duplicate_similarity();
+
+        candidates
+            .iter()
+            .map(|(fingerprint, candidate)| (self.similarity(fingerprint), candidate))
+            .filter(|(score, _)| *score >= threshold)
+            .max_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map(|(_, candidate)| candidate)
+    }
+}
+
+/// Turn what the user typed into a full text search query. Every word is quoted so nothing is
+/// treated as query syntax, and matches words that start with it.
+fn search_terms(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Reduce quote text to its words so copies that only differ in case, spacing or punctuation
+/// compare equal. Apostrophes, including smart ones, are dropped so "don't" matches "dont".
+fn normalize_quote(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '‘' | '’' | '‛' | '`'))
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_quote_ignores_case_spacing_and_punctuation() {
+        assert_eq!(normalize_quote("  Hello,   WORLD!! "), "hello world");
+        assert_eq!(normalize_quote("well...\nok?"), "well ok");
+        assert_eq!(normalize_quote("¡Ça va!"), "ça va");
+    }
+
+    #[test]
+    fn normalize_quote_drops_apostrophes() {
+        assert_eq!(normalize_quote("Don't"), "dont");
+        assert_eq!(normalize_quote("don’t"), "dont");
+        assert_eq!(normalize_quote("it`s"), "its");
+    }
+
+    #[test]
+    fn normalize_quote_of_only_punctuation_is_empty() {
+        assert_eq!(normalize_quote("?!... --"), "");
+    }
+
+    #[test]
+    fn emoji_only_quotes_are_not_duplicates() {
+        let laughing = Fingerprint::new("😂");
+        let skull = Fingerprint::new("💀");
+        assert_eq!(laughing.similarity(&skull), 0.0);
+        assert_eq!(laughing.most_similar(&[(skull, 1)]), None);
+        assert_eq!(
+            Fingerprint::new("!!!").similarity(&Fingerprint::new("???")),
+            0.0
+        );
+    }
+
+    fn quote(id: i32, author: &str, text: &str, author_user_id: Option<i64>) -> QuoteMessage {
+        QuoteMessage {
+            id,
+            quote: text.to_string(),
+            author: author.to_string(),
+            author_user_id,
+            timestamp: None,
+            source_link: None,
+            submitted_by: None,
+            created_at: None,
+            guild_id: Some(1),
+            lines: Vec::new(),
+            linked_user_id: None,
+        }
+    }
+
+    #[test]
+    fn fake_quotes_of_a_shared_name_come_from_whoever_has_most_quotes() {
+        let mut fakes = FakeQuotes::default();
+        let aliases = HashMap::new();
+        fakes.train(&quote(1, "Sam", "first sam speaks", Some(1)), &aliases);
+        fakes.train(&quote(2, "Sam", "first sam again", Some(1)), &aliases);
+        fakes.train(&quote(3, "sam", "second sam here", Some(2)), &aliases);
+
+        let chain = fakes.person(" SAM ").unwrap();
+        let fake = chain.generate(&mut thread_rng(), 10).unwrap();
+        assert!(fake.starts_with("first sam"), "{}", fake);
+        assert!(fakes.person("nobody").is_none());
+    }
+
+    #[test]
+    fn search_terms_match_word_prefixes() {
+        assert_eq!(search_terms("hello  world"), "\"hello\"* \"world\"*");
+    }
+
+    #[test]
+    fn search_terms_quote_query_syntax() {
+        assert_eq!(search_terms("a OR b"), "\"a\"* \"OR\"* \"b\"*");
+        assert_eq!(search_terms("-not col:x"), "\"-not\"* \"col:x\"*");
+        assert_eq!(search_terms("NEAR(a b)*"), "\"NEAR(a\"* \"b)*\"*");
+    }
+
+    #[test]
+    fn search_terms_escape_quotes() {
+        assert_eq!(search_terms("say \"hi\""), "\"say\"* \"\"\"hi\"\"\"*");
+    }
+
+    #[test]
+    fn search_terms_of_blank_text_are_empty() {
+        assert_eq!(search_terms(" \n\t "), "");
+    }
+
+    #[test]
+    fn identical_quotes_are_fully_similar() {
+        let a = Fingerprint::new("I don't like sand.");
+        let b = Fingerprint::new("i dont like SAND");
+        assert_eq!(a.similarity(&b), 1.0);
+    }
+
+    #[test]
+    fn unrelated_quotes_are_not_similar() {
+        let a = Fingerprint::new("abc");
+        let b = Fingerprint::new("xyz");
+        assert_eq!(a.similarity(&b), 0.0);
+    }
+
+    #[test]
+    fn similarity_is_the_dice_coefficient_of_bigrams() {
+        // "night" and "nacht" share only the bigram "ht" out of 4 each
+        let a = Fingerprint::new("night");
+        let b = Fingerprint::new("nacht");
+        assert_eq!(a.similarity(&b), 0.25);
+        assert_eq!(b.similarity(&a), 0.25);
+    }
+
+    #[test]
+    fn single_characters_are_only_similar_when_equal() {
+        assert_eq!(
+            Fingerprint::new("a").similarity(&Fingerprint::new("b")),
+            0.0
+        );
+        assert_eq!(
+            Fingerprint::new("a").similarity(&Fingerprint::new("A!")),
+            1.0
+        );
+    }
+
+    #[test]
+    fn most_similar_picks_the_closest_match_over_the_threshold() {
+        let candidates = vec![
+            (Fingerprint::new("something else entirely"), 1),
+            (
+                Fingerprint::new("the quick brown fox jumps over the lazy dog"),
+                2,
+            ),
+            (
+                Fingerprint::new("the quick brown fox jumped over the lazy dog"),
+                3,
+            ),
+        ];
+        let quote = Fingerprint::new("The quick brown fox jumps over the lazy dog!");
+        assert_eq!(quote.most_similar(&candidates), Some(&2));
+    }
 
-        Ok(quote)
+    #[test]
+    fn most_similar_ignores_quotes_under_the_threshold() {
+        let candidates = vec![(Fingerprint::new("the quick brown fox"), 1)];
+        let quote = Fingerprint::new("a slow green turtle");
+        assert_eq!(quote.most_similar(&candidates), None);
     }
 }
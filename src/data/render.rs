@@ -0,0 +1,104 @@
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{ChannelId, Context, GuildId, RoleId, UserId};
+use regex::Regex;
+
+/// Replace raw user, role and channel mentions with their names, and custom emoji with their
+/// `:name:` form, so content reads well outside of the channel it was posted in.
+pub async fn resolve_mentions(ctx: &Context, guild_id: Option<GuildId>, content: &str) -> String {
+    let pattern = Regex::new(r"<(@!?|@&|#)(\d+)>|<a?:(\w+):\d+>").unwrap();
+
+    let mut rendered = String::with_capacity(content.len());
+    let mut last = 0;
+    for captures in pattern.captures_iter(content) {
+        let token = captures.get(0).unwrap();
+        rendered.push_str(&content[last..token.start()]);
+
+        let replacement = match (captures.get(1), captures.get(2), captures.get(3)) {
+            (_, _, Some(emoji)) => Some(format!(":{}:", emoji.as_str())),
+            (Some(kind), Some(id), _) => match id.as_str().parse::<u64>() {
+                Ok(id) if id != 0 => resolve_mention(ctx, guild_id, kind.as_str(), id).await,
+                _ => None,
+            },
+            _ => None,
+        };
+
+        // Leave anything we couldn't resolve as it was
+        rendered.push_str(replacement.as_deref().unwrap_or(token.as_str()));
+        last = token.end();
+    }
+    rendered.push_str(&content[last..]);
+
+    rendered
+}
+
+async fn resolve_mention(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    kind: &str,
+    id: u64,
+) -> Option<String> {
+    match kind {
+        "@" | "@!" => resolve_author(ctx, guild_id, UserId::new(id))
+            .await
+            .map(|(name, _)| format!("@{}", name)),
+        "@&" => guild_id?
+            .roles(&ctx.http)
+            .await
+            .ok()?
+            .get(&RoleId::new(id))
+            .map(|role| format!("@{}", role.name)),
+        "#" => match ChannelId::new(id).to_channel(ctx).await.ok()? {
+            serenity::Channel::Guild(channel) => Some(format!("#{}", channel.name)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Look up a user's display name and avatar, preferring their nickname in the guild.
+pub async fn resolve_author(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+) -> Option<(String, String)> {
+    if let Some(guild_id) = guild_id {
+        if let Ok(member) = guild_id.member(ctx, user_id).await {
+            return Some((member.display_name().to_string(), member.face()));
+        }
+    }
+
+    let user = user_id.to_user(ctx).await.ok()?;
+    let name = user
+        .global_name
+        .clone()
+        .unwrap_or_else(|| user.name.clone());
+    Some((name, user.face()))
+}
+
+/// Truncate content to at most `limit` characters, ending with a link to the full message.
+/// Spoilers and code blocks cut off part way through are closed again.
+pub fn truncate_with_link(content: &str, limit: usize, link: &str) -> String {
+    if content.chars().count() <= limit {
+        return content.to_string();
+    }
+
+    let read_more = format!("… [read more]({})", link);
+    // Leave room for closing any formatting we cut through
+    let budget = limit.saturating_sub(read_more.chars().count() + "\n```||".len());
+    let cut: String = content.chars().take(budget).collect();
+
+    // Prefer to cut between words so mentions and links aren't split
+    let mut truncated = match cut.rfind(char::is_whitespace) {
+        Some(index) if index > cut.len() / 2 => cut[..index].to_string(),
+        _ => cut,
+    };
+
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.push_str("\n```");
+    }
+    if truncated.matches("||").count() % 2 == 1 {
+        truncated.push_str("||");
+    }
+
+    format!("{}{}", truncated, read_more)
+}
@@ -3,30 +3,195 @@ use std::sync::Arc;
 
 use crate::data::db;
 
+use chrono::Utc;
+use poise::serenity_prelude as serenity;
 use sqlx::{FromRow, Pool, Sqlite};
 use tokio::sync::Mutex;
 
 use super::db::BotDatabase;
 
+/// Custom ID prefix of the components on request voting menus, followed by the action.
+pub const REQUEST_MENU_PREFIX: &str = "request_vote:";
+// Requests listed on each page of the voting menu
+const REQUESTS_PER_PAGE: i64 = 10;
+// Longest label a select menu option can have
+const MAX_OPTION_LENGTH: usize = 100;
+// Longest a request's text gets in listings, so 25 of them fit in an embed
+const MAX_LISTED_LENGTH: usize = 80;
+
+/// Selects requests with their vote counts, for adding conditions to.
+const SELECT_REQUESTS: &str = "SELECT id, request, user, status, status_reason,
+    COALESCE(legacy_votes, 0)
+        + (SELECT COUNT(*) FROM request_votes WHERE request_id = requests.id) AS votes
+    FROM requests";
+/// Adds an entry to a request's timeline.
+const RECORD_STATUS_CHANGE: &str = "INSERT INTO request_status_changes
+    (request_id, status, reason, changed_by, changed_at) VALUES (?, ?, ?, ?, ?)";
+/// Matches requests that are still being considered or worked on.
+const ACTIVE_REQUESTS: &str = "status IN ('open', 'planned', 'in-progress')";
+
 #[derive(FromRow, Debug, Clone, PartialEq, Eq)]
 pub struct FeatureRequest {
     pub id: i32,
     pub request: String,
     pub user: String,
     pub votes: i32,
+    pub status: RequestStatus,
+    /// Why the request was last moved to its status, if anyone said.
+    pub status_reason: Option<String>,
+}
+
+/// Where a request is in its lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type, poise::ChoiceParameter)]
+#[sqlx(rename_all = "kebab-case")]
+pub enum RequestStatus {
+    #[name = "Open"]
+    Open,
+    #[name = "Planned"]
+    Planned,
+    #[name = "In progress"]
+    InProgress,
+    #[name = "Done"]
+    Done,
+    #[name = "Rejected"]
+    Rejected,
+}
+
+impl RequestStatus {
+    /// Whether the request is finished with, one way or another. Closed requests can't be
+    /// voted for.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, RequestStatus::Done | RequestStatus::Rejected)
+    }
+}
+
+impl fmt::Display for RequestStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let status = match self {
+            RequestStatus::Open => "Open",
+            RequestStatus::Planned => "Planned",
+            RequestStatus::InProgress => "In progress",
+            RequestStatus::Done => "Done",
+            RequestStatus::Rejected => "Rejected",
+        };
+        write!(f, "{}", status)
+    }
+}
+
+/// A status a request was moved to, for its timeline.
+#[derive(FromRow, Debug, Clone)]
+pub struct RequestStatusChange {
+    pub status: RequestStatus,
+    pub reason: Option<String>,
+    pub changed_by: Option<i64>,
+    /// Missing for requests filed before their history was kept.
+    pub changed_at: Option<f64>,
+}
+
+/// One page of the request voting menu, most voted requests first.
+#[derive(Debug, Clone)]
+pub struct RequestPage {
+    pub requests: Vec<FeatureRequest>,
+    /// Page number, from 0.
+    pub page: i64,
+    pub page_count: i64,
+}
+
+impl RequestPage {
+    /// Create the voting menu for this page: an embed listing the requests, a menu to vote
+    /// for one of them, and buttons to change page. Everything the menu needs is in its
+    /// custom IDs, so it keeps working for anyone after the bot restarts.
+    pub fn create_menu(&self) -> (serenity::CreateEmbed, Vec<serenity::CreateActionRow>) {
+        let description = match self.requests.is_empty() {
+            true => "No requests yet, add one with `/request add`!".to_string(),
+            false => self
+                .requests
+                .iter()
+                .map(FeatureRequest::summary)
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        let embed = serenity::CreateEmbed::default()
+            .title("Feature requests")
+            .description(description)
+            .footer(serenity::CreateEmbedFooter::new(format!(
+                "Page {} of {} · Pick a request you've voted for to take your vote back",
+                self.page + 1,
+                self.page_count
+            )));
+
+        let mut components = Vec::new();
+        if !self.requests.is_empty() {
+            let options = self
+                .requests
+                .iter()
+                .map(|request| {
+                    let label = format!("{}. {}", request.id, request.request)
+                        .chars()
+                        .take(MAX_OPTION_LENGTH)
+                        .collect::<String>();
+                    serenity::CreateSelectMenuOption::new(label, request.id.to_string())
+                        .description(format!("Votes: {}", request.votes))
+                })
+                .collect();
+            components.push(serenity::CreateActionRow::SelectMenu(
+                serenity::CreateSelectMenu::new(
+                    format!("{}select:{}", REQUEST_MENU_PREFIX, self.page),
+                    serenity::CreateSelectMenuKind::String { options },
+                )
+                .placeholder("Vote for a request"),
+            ));
+        }
+
+        components.push(serenity::CreateActionRow::Buttons(vec![
+            serenity::CreateButton::new(format!("{}prev:{}", REQUEST_MENU_PREFIX, self.page))
+                .emoji('◀')
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(self.page == 0),
+            serenity::CreateButton::new(format!("{}next:{}", REQUEST_MENU_PREFIX, self.page))
+                .emoji('▶')
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(self.page + 1 >= self.page_count),
+            serenity::CreateButton::new(format!("{}mine", REQUEST_MENU_PREFIX))
+                .label("My votes")
+                .style(serenity::ButtonStyle::Primary),
+        ]));
+
+        (embed, components)
+    }
 }
 
 pub struct Requests {
     db: Arc<Mutex<BotDatabase>>,
 }
 
+impl FeatureRequest {
+    /// The request as shown in listings, with long requests shortened.
+    pub fn summary(&self) -> String {
+        let mut summary = self.clone();
+        if self.request.chars().count() > MAX_LISTED_LENGTH {
+            summary.request = self
+                .request
+                .chars()
+                .take(MAX_LISTED_LENGTH)
+                .collect::<String>()
+                + "…";
+        }
+        summary.to_string()
+    }
+}
+
 impl fmt::Display for FeatureRequest {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
             "{}. {} *[<@{}>] (votes: {})*",
             self.id, self.request, self.user, self.votes
-        )
+        )?;
+        if self.status != RequestStatus::Open {
+            write!(f, " **{}**", self.status)?;
+        }
+        Ok(())
     }
 }
 
@@ -41,7 +206,7 @@ impl Requests {
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 request TEXT NOT NULL,
                 user TEXT NOT NULL,
-                votes INTEGER DEFAULT 0
+                legacy_votes INTEGER DEFAULT 0
             )
         "#;
         sqlx::query(query).execute(conn).await?;
@@ -49,17 +214,82 @@ impl Requests {
         Ok(())
     }
 
-    /// Return a vector of all active requests.
-    pub async fn get_requests(&self) -> Result<Vec<FeatureRequest>, sqlx::Error> {
+    /// Return a page of the active requests for the voting menu. Pages past the end give
+    /// the last page.
+    pub async fn get_request_page(&self, page: i64) -> Result<RequestPage, sqlx::Error> {
         let db_lock = self.db.lock().await;
         let conn = db_lock.get_conn();
 
-        let requests: Vec<FeatureRequest> =
-            sqlx::query_as("SELECT * FROM requests ORDER BY votes DESC LIMIT 20")
-                .fetch_all(conn)
-                .await?;
+        let query = format!("SELECT COUNT(*) FROM requests WHERE {}", ACTIVE_REQUESTS);
+        let (total,) = sqlx::query_as::<_, (i64,)>(&query).fetch_one(conn).await?;
+        let page_count = ((total + REQUESTS_PER_PAGE - 1) / REQUESTS_PER_PAGE).max(1);
+        let page = page.clamp(0, page_count - 1);
+
+        let query = format!(
+            "{} WHERE {} ORDER BY votes DESC, id LIMIT ? OFFSET ?",
+            SELECT_REQUESTS, ACTIVE_REQUESTS
+        );
+        let requests: Vec<FeatureRequest> = sqlx::query_as(&query)
+            .bind(REQUESTS_PER_PAGE)
+            .bind(page * REQUESTS_PER_PAGE)
+            .fetch_all(conn)
+            .await?;
+
+        Ok(RequestPage {
+            requests,
+            page,
+            page_count,
+        })
+    }
+
+    /// Return the requests with a status, or the active ones, most voted first.
+    pub async fn get_requests(
+        &self,
+        status: Option<RequestStatus>,
+        limit: i64,
+    ) -> Result<Vec<FeatureRequest>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let query = format!(
+            "{} WHERE (?1 IS NULL AND {}) OR status = ?1 ORDER BY votes DESC, id LIMIT ?2",
+            SELECT_REQUESTS, ACTIVE_REQUESTS
+        );
+        sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(status)
+            .bind(limit)
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Return a request, whatever its status.
+    pub async fn get_request(&self, id: i32) -> Result<Option<FeatureRequest>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
 
-        Ok(requests)
+        let query = format!("{} WHERE id = ?", SELECT_REQUESTS);
+        sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(id)
+            .fetch_optional(conn)
+            .await
+    }
+
+    /// Return every status a request has been in, oldest first.
+    pub async fn get_request_history(
+        &self,
+        id: i32,
+    ) -> Result<Vec<RequestStatusChange>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        sqlx::query_as::<_, RequestStatusChange>(
+            "SELECT status, reason, changed_by, changed_at FROM request_status_changes
+            WHERE request_id = ?
+            ORDER BY id",
+        )
+        .bind(id)
+        .fetch_all(conn)
+        .await
     }
 
     /// Add a new request to the database
@@ -73,53 +303,194 @@ impl Requests {
 
         self.init(conn).await?;
 
-        let query = "INSERT INTO requests (request, user, votes) VALUES (?, ?, 0)";
-        sqlx::query(query)
+        let mut tx = conn.begin().await?;
+
+        let query = "INSERT INTO requests (request, user) VALUES (?, ?)";
+        let id = sqlx::query(query)
             .bind(req.clone())
             .bind(user.clone())
-            .execute(conn)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+
+        sqlx::query(RECORD_STATUS_CHANGE)
+            .bind(id)
+            .bind(RequestStatus::Open)
+            .bind(None::<String>)
+            .bind(user.parse::<i64>().ok())
+            .bind(Utc::now().timestamp() as f64)
+            .execute(&mut *tx)
             .await?;
 
         // readback the stored req
-        let query = "SELECT * FROM requests ORDER BY id DESC LIMIT 1";
-        let req = sqlx::query_as::<_, FeatureRequest>(query)
-            .fetch_one(conn)
+        let query = format!("{} WHERE id = ?", SELECT_REQUESTS);
+        let req = sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(id)
+            .fetch_one(&mut *tx)
             .await?;
 
+        tx.commit().await?;
+
         Ok(req)
     }
 
-    /// Mark a request as complete by deleting it from the database
-    pub async fn complete_request(&self, id: i32) -> Result<FeatureRequest, sqlx::Error> {
+    /// Move a request to a new status, adding it to the request's timeline. Returns the
+    /// updated request and the status it had before, or `None` if it doesn't exist.
+    pub async fn set_request_status(
+        &self,
+        id: i32,
+        status: RequestStatus,
+        reason: Option<String>,
+        changed_by: serenity::UserId,
+    ) -> Result<Option<(FeatureRequest, RequestStatus)>, sqlx::Error> {
         let db_lock = self.db.lock().await;
-        let conn = db_lock.get_conn();
+        let mut tx = db_lock.get_conn().begin().await?;
 
-        let read_req =
-            sqlx::query_as::<_, FeatureRequest>("SELECT * FROM requests WHERE id = ? LIMIT 1")
+        let previous =
+            sqlx::query_as::<_, (RequestStatus,)>("SELECT status FROM requests WHERE id = ?")
                 .bind(id)
-                .fetch_one(conn)
+                .fetch_optional(&mut *tx)
                 .await?;
+        let Some((previous,)) = previous else {
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE requests SET status = ?, status_reason = ? WHERE id = ?")
+            .bind(status)
+            .bind(&reason)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(RECORD_STATUS_CHANGE)
+            .bind(id)
+            .bind(status)
+            .bind(&reason)
+            .bind(changed_by.get() as i64)
+            .bind(Utc::now().timestamp() as f64)
+            .execute(&mut *tx)
+            .await?;
+
+        let query = format!("{} WHERE id = ?", SELECT_REQUESTS);
+        let req = sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
 
-        let query = format!("DELETE FROM requests WHERE id = {}", id);
-        sqlx::query(&query).execute(conn).await?;
+        tx.commit().await?;
 
-        Ok(read_req)
+        Ok(Some((req, previous)))
     }
 
-    /// Vote for a request
-    pub async fn vote_request(&self, id: i32) -> Result<FeatureRequest, sqlx::Error> {
+    /// Vote for a request, or take the vote back if the user has already voted for it.
+    /// Returns the request and whether the user is now voting for it, or `None` if the
+    /// request doesn't exist or is closed.
+    pub async fn vote_request(
+        &self,
+        id: i32,
+        user_id: serenity::UserId,
+    ) -> Result<Option<(FeatureRequest, bool)>, sqlx::Error> {
         let db_lock = self.db.lock().await;
-        let conn = db_lock.get_conn();
+        let mut tx = db_lock.get_conn().begin().await?;
 
-        let query = "UPDATE requests SET votes = votes + 1 WHERE id = ?";
-        sqlx::query(query).bind(id).execute(conn).await?;
+        let status =
+            sqlx::query_as::<_, (RequestStatus,)>("SELECT status FROM requests WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .await?;
+        if !matches!(status, Some((status,)) if !status.is_closed()) {
+            return Ok(None);
+        }
 
-        let query = "SELECT * FROM requests WHERE id = ? LIMIT 1";
-        let req = sqlx::query_as::<_, FeatureRequest>(query)
+        let removed = sqlx::query("DELETE FROM request_votes WHERE request_id = ? AND user_id = ?")
             .bind(id)
-            .fetch_one(conn)
+            .bind(user_id.get() as i64)
+            .execute(&mut *tx)
             .await?;
+        let voted = removed.rows_affected() == 0;
+        if voted {
+            sqlx::query("INSERT INTO request_votes (request_id, user_id) VALUES (?, ?)")
+                .bind(id)
+                .bind(user_id.get() as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
 
-        Ok(req)
+        let query = format!("{} WHERE id = ? LIMIT 1", SELECT_REQUESTS);
+        let req = sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(Some((req, voted)))
+    }
+
+    /// Return the active requests a user has voted for, most voted first.
+    pub async fn get_voted_requests(
+        &self,
+        user_id: serenity::UserId,
+    ) -> Result<Vec<FeatureRequest>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let query = format!(
+            "{} WHERE id IN (SELECT request_id FROM request_votes WHERE user_id = ?) AND {}
+            ORDER BY votes DESC, id",
+            SELECT_REQUESTS, ACTIVE_REQUESTS
+        );
+        sqlx::query_as::<_, FeatureRequest>(&query)
+            .bind(user_id.get() as i64)
+            .fetch_all(conn)
+            .await
+    }
+
+    /// Return who should hear about a request changing status: whoever filed it and everyone
+    /// who voted for it, except anyone who's turned notifications off.
+    pub async fn get_notification_recipients(
+        &self,
+        id: i32,
+    ) -> Result<Vec<serenity::UserId>, sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let recipients = sqlx::query_as::<_, (i64,)>(
+            "SELECT user_id FROM (
+                SELECT CAST(user AS INTEGER) AS user_id FROM requests WHERE id = ?1
+                UNION SELECT user_id FROM request_votes WHERE request_id = ?1
+            )
+            WHERE user_id > 0
+                AND user_id NOT IN (SELECT user_id FROM request_notification_opt_outs)",
+        )
+        .bind(id)
+        .fetch_all(conn)
+        .await?;
+
+        Ok(recipients
+            .into_iter()
+            .map(|(user_id,)| serenity::UserId::new(user_id as u64))
+            .collect())
+    }
+
+    /// Turn notifications about requests on or off for a user.
+    pub async fn set_notifications(
+        &self,
+        user_id: serenity::UserId,
+        enabled: bool,
+    ) -> Result<(), sqlx::Error> {
+        let db_lock = self.db.lock().await;
+        let conn = db_lock.get_conn();
+
+        let query = match enabled {
+            true => "DELETE FROM request_notification_opt_outs WHERE user_id = ?",
+            false => "INSERT OR IGNORE INTO request_notification_opt_outs (user_id) VALUES (?)",
+        };
+        sqlx::query(query)
+            .bind(user_id.get() as i64)
+            .execute(conn)
+            .await?;
+
+        Ok(())
     }
 }
@@ -0,0 +1,190 @@
+use crate::constants::{get_nsfw_starboard_channel_id, get_private_starboard_channel_id};
+
+use log::{debug, warn};
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{ChannelId, Context, Permissions, RoleId};
+use sqlx::FromRow;
+use std::collections::HashMap;
+
+/// NSFW flag and visibility of a channel, so bestofs aren't reposted somewhere less restricted
+/// than where they were posted.
+#[derive(FromRow, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelRestriction {
+    pub id: i64,
+    pub nsfw: bool,
+    pub public: bool,
+}
+
+impl ChannelRestriction {
+    /// Work out the restrictions of a guild channel, given the guild's @everyone role.
+    pub fn from_guild_channel(
+        channel: &serenity::GuildChannel,
+        everyone: Option<&serenity::Role>,
+    ) -> ChannelRestriction {
+        let mut permissions = everyone.map_or(Permissions::VIEW_CHANNEL, |role| role.permissions);
+
+        // The @everyone role shares its ID with the guild
+        let everyone_overwrite =
+            serenity::PermissionOverwriteType::Role(RoleId::new(channel.guild_id.get()));
+        for overwrite in &channel.permission_overwrites {
+            if overwrite.kind == everyone_overwrite {
+                permissions = (permissions & !overwrite.deny) | overwrite.allow;
+            }
+        }
+
+        ChannelRestriction {
+            id: channel.id.get() as i64,
+            nsfw: channel.nsfw,
+            public: permissions.contains(Permissions::VIEW_CHANNEL),
+        }
+    }
+
+    /// Look up the restrictions of any channel through the API. Threads take after their parent
+    /// channel, since they have no permission overwrites of their own.
+    pub async fn fetch(
+        ctx: &Context,
+        channel_id: ChannelId,
+    ) -> Result<ChannelRestriction, serenity::Error> {
+        let channel = match channel_id.to_channel(ctx).await? {
+            serenity::Channel::Guild(channel) => channel,
+            // Anyone could be on the other end of a DM, so treat it like a public channel
+            _ => {
+                return Ok(ChannelRestriction {
+                    id: channel_id.get() as i64,
+                    nsfw: false,
+                    public: true,
+                })
+            }
+        };
+
+        let roles = channel.guild_id.roles(&ctx.http).await?;
+        let everyone = roles.get(&RoleId::new(channel.guild_id.get()));
+        if channel.thread_metadata.is_none() {
+            return Ok(ChannelRestriction::from_guild_channel(&channel, everyone));
+        }
+
+        let parent = match channel.parent_id {
+            Some(parent_id) => parent_id.to_channel(ctx).await?.guild(),
+            None => None,
+        }
+        .ok_or(serenity::Error::Other("Thread without a parent channel"))?;
+        let parent = ChannelRestriction::from_guild_channel(&parent, everyone);
+
+        Ok(ChannelRestriction {
+            id: channel_id.get() as i64,
+            nsfw: channel.nsfw || parent.nsfw,
+            public: parent.public && channel.kind != serenity::ChannelType::PrivateThread,
+        })
+    }
+
+    /// Whether content from the source channel can be posted in this one.
+    pub fn can_receive(&self, source: &ChannelRestriction) -> bool {
+        (self.nsfw || !source.nsfw) && (!self.public || source.public)
+    }
+}
+
+/// Look up a channel's restrictions, preferring the ones recorded while scanning.
+async fn lookup(
+    ctx: &Context,
+    restrictions: &HashMap<ChannelId, ChannelRestriction>,
+    channel_id: ChannelId,
+) -> Result<ChannelRestriction, serenity::Error> {
+    match restrictions.get(&channel_id) {
+        Some(restriction) => Ok(*restriction),
+        None => ChannelRestriction::fetch(ctx, channel_id).await,
+    }
+}
+
+/// Pick the channel to repost content from `source` to. The destination is used when it's at
+/// least as restricted as the source, otherwise a matching restricted starboard if one is
+/// configured. Returns None when there's nowhere safe to post it.
+pub async fn route(
+    ctx: &Context,
+    restrictions: &HashMap<ChannelId, ChannelRestriction>,
+    source: ChannelId,
+    destination: ChannelId,
+) -> Option<ChannelId> {
+    let source_restriction = match lookup(ctx, restrictions, source).await {
+        Ok(restriction) => restriction,
+        Err(why) => {
+            warn!("Failed to look up restrictions of {:?}: {:?}", source, why);
+            return None;
+        }
+    };
+
+    let mut candidates = vec![destination];
+    if source_restriction.nsfw {
+        candidates.extend(get_nsfw_starboard_channel_id().map(ChannelId::new));
+    }
+    if !source_restriction.public {
+        candidates.extend(get_private_starboard_channel_id().map(ChannelId::new));
+    }
+
+    for candidate in candidates {
+        match lookup(ctx, restrictions, candidate).await {
+            Ok(restriction) if restriction.can_receive(&source_restriction) => {
+                return Some(candidate)
+            }
+            Ok(_) => debug!(
+                "Not posting content from {:?} to less restricted {:?}",
+                source, candidate
+            ),
+            Err(why) => warn!(
+                "Failed to look up restrictions of {:?}: {:?}",
+                candidate, why
+            ),
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(nsfw: bool, public: bool) -> ChannelRestriction {
+        ChannelRestriction {
+            id: 1,
+            nsfw,
+            public,
+        }
+    }
+
+    #[test]
+    fn unrestricted_content_can_go_anywhere() {
+        let source = channel(false, true);
+        for destination in [
+            channel(false, true),
+            channel(false, false),
+            channel(true, true),
+            channel(true, false),
+        ] {
+            assert!(destination.can_receive(&source), "{:?}", destination);
+        }
+    }
+
+    #[test]
+    fn nsfw_content_only_goes_to_nsfw_channels() {
+        let source = channel(true, true);
+        assert!(!channel(false, true).can_receive(&source));
+        assert!(!channel(false, false).can_receive(&source));
+        assert!(channel(true, true).can_receive(&source));
+    }
+
+    #[test]
+    fn private_content_only_goes_to_private_channels() {
+        let source = channel(false, false);
+        assert!(!channel(false, true).can_receive(&source));
+        assert!(!channel(true, true).can_receive(&source));
+        assert!(channel(false, false).can_receive(&source));
+    }
+
+    #[test]
+    fn private_nsfw_content_needs_both_restrictions() {
+        let source = channel(true, false);
+        assert!(!channel(true, true).can_receive(&source));
+        assert!(!channel(false, false).can_receive(&source));
+        assert!(channel(true, false).can_receive(&source));
+    }
+}
@@ -1,4 +1,8 @@
 pub mod mentionme;
+pub mod quotevote;
+pub mod requestvote;
+use crate::data::quotes::VOTE_BUTTON_PREFIX;
+use crate::data::requests::REQUEST_MENU_PREFIX;
 use crate::data::Data;
 
 use crate::Error;
@@ -17,10 +21,29 @@ pub async fn handle_event(
     );
 
     // Match on the event type
-    if let serenity::FullEvent::Message { new_message } = event {
-        if let Err(why) = handle_message_event(ctx, new_message, data).await {
-            error!("Failed to handle message: {:?}", why);
+    match event {
+        serenity::FullEvent::Message { new_message } => {
+            if let Err(why) = handle_message_event(ctx, new_message, data).await {
+                error!("Failed to handle message: {:?}", why);
+            }
         }
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(interaction),
+        } if interaction.data.custom_id.starts_with(VOTE_BUTTON_PREFIX) => {
+            if let Err(why) = quotevote::handle_vote_interaction(ctx, interaction, data).await {
+                error!("Failed to handle quote vote: {:?}", why);
+            }
+        }
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(interaction),
+        } if interaction.data.custom_id.starts_with(REQUEST_MENU_PREFIX) => {
+            if let Err(why) =
+                requestvote::handle_request_menu_interaction(ctx, interaction, data).await
+            {
+                error!("Failed to handle request menu: {:?}", why);
+            }
+        }
+        _ => {}
     }
     Ok(())
 }
@@ -0,0 +1,49 @@
+use crate::data::quotes::VOTE_BUTTON_PREFIX;
+use crate::data::Data;
+use crate::Error;
+use log::info;
+use poise::serenity_prelude as serenity;
+
+/// Handler for presses of the rating buttons on quote embeds.
+pub async fn handle_vote_interaction(
+    ctx: serenity::Context,
+    interaction: serenity::ComponentInteraction,
+    data: &Data,
+) -> Result<(), Error> {
+    let (quote_id, vote) = interaction
+        .data
+        .custom_id
+        .strip_prefix(VOTE_BUTTON_PREFIX)
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(id, vote)| Some((id.parse::<i32>().ok()?, vote.parse::<i64>().ok()?)))
+        .filter(|(_, vote)| *vote == 1 || *vote == -1)
+        .ok_or("Malformed quote vote button")?;
+
+    let votes = data
+        .quotes
+        .lock()
+        .await
+        .vote(quote_id, interaction.user.id, vote)
+        .await?;
+
+    let response = match votes {
+        Some(votes) => {
+            info!(
+                "{} voted {} on quote {}",
+                interaction.user.name, vote, quote_id
+            );
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .components(vec![votes.create_buttons(quote_id)]),
+            )
+        }
+        None => serenity::CreateInteractionResponse::Message(
+            serenity::CreateInteractionResponseMessage::new()
+                .content("That quote has been deleted.")
+                .ephemeral(true),
+        ),
+    };
+    interaction.create_response(&ctx.http, response).await?;
+
+    Ok(())
+}
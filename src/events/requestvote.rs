@@ -0,0 +1,142 @@
+use crate::data::requests::{FeatureRequest, REQUEST_MENU_PREFIX};
+use crate::data::Data;
+use crate::Error;
+use log::info;
+use poise::serenity_prelude as serenity;
+
+// Voted requests listed at once, as many as fit in a message
+const LISTED_VOTES: usize = 10;
+
+/// Handler for the request voting menu: voting from the select menu, changing page, and
+/// listing the requests the user has voted for.
+pub async fn handle_request_menu_interaction(
+    ctx: serenity::Context,
+    interaction: serenity::ComponentInteraction,
+    data: &Data,
+) -> Result<(), Error> {
+    let action = interaction
+        .data
+        .custom_id
+        .strip_prefix(REQUEST_MENU_PREFIX)
+        .ok_or("Malformed request menu component")?;
+
+    if action == "mine" {
+        return show_voted_requests(ctx, interaction, data).await;
+    }
+
+    let (action, page) = action
+        .split_once(':')
+        .and_then(|(action, page)| Some((action, page.parse::<i64>().ok()?)))
+        .ok_or("Malformed request menu component")?;
+
+    let (page, voted) = match action {
+        "prev" => (page - 1, None),
+        "next" => (page + 1, None),
+        "select" => {
+            let id = match &interaction.data.kind {
+                serenity::ComponentInteractionDataKind::StringSelect { values } => {
+                    values.first().and_then(|id| id.parse::<i32>().ok())
+                }
+                _ => None,
+            }
+            .ok_or("Malformed request menu selection")?;
+
+            let voted = data
+                .requests
+                .lock()
+                .await
+                .vote_request(id, interaction.user.id)
+                .await?;
+            (page, Some(voted))
+        }
+        _ => return Err("Unknown request menu action".into()),
+    };
+
+    // Refresh the menu for everyone, then tell the voter what happened
+    let (embed, components) = data
+        .requests
+        .lock()
+        .await
+        .get_request_page(page)
+        .await?
+        .create_menu();
+    interaction
+        .create_response(
+            &ctx.http,
+            serenity::CreateInteractionResponse::UpdateMessage(
+                serenity::CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .components(components),
+            ),
+        )
+        .await?;
+
+    let message = match voted {
+        None => return Ok(()),
+        Some(Some((request, true))) => {
+            info!("{} voted for request {}", interaction.user.name, request.id);
+            format!("Voted for **{}**!", request.request)
+        }
+        Some(Some((request, false))) => {
+            info!(
+                "{} took back their vote for request {}",
+                interaction.user.name, request.id
+            );
+            format!("Took back your vote for **{}**.", request.request)
+        }
+        Some(None) => "That request is closed.".to_string(),
+    };
+    interaction
+        .create_followup(
+            &ctx.http,
+            serenity::CreateInteractionResponseFollowup::new()
+                .content(message)
+                .ephemeral(true),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Privately list the active requests the user has voted for.
+async fn show_voted_requests(
+    ctx: serenity::Context,
+    interaction: serenity::ComponentInteraction,
+    data: &Data,
+) -> Result<(), Error> {
+    let requests = data
+        .requests
+        .lock()
+        .await
+        .get_voted_requests(interaction.user.id)
+        .await?;
+
+    let mut content = match requests.is_empty() {
+        true => "You haven't voted for any active requests.".to_string(),
+        false => format!(
+            "*You've voted for:*\n{}",
+            requests
+                .iter()
+                .take(LISTED_VOTES)
+                .map(FeatureRequest::summary)
+                .collect::<Vec<_>>()
+                .join("\n")
+        ),
+    };
+    if requests.len() > LISTED_VOTES {
+        content.push_str(&format!("\n*…and {} more*", requests.len() - LISTED_VOTES));
+    }
+    interaction
+        .create_response(
+            &ctx.http,
+            serenity::CreateInteractionResponse::Message(
+                serenity::CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .allowed_mentions(serenity::CreateAllowedMentions::new())
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
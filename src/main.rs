@@ -12,6 +12,7 @@ use tracing::instrument;
 // Types used by all command functions
 type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, data::Data, Error>;
+type ApplicationContext<'a> = poise::ApplicationContext<'a, data::Data, Error>;
 
 #[instrument(skip(error))]
 async fn on_error(error: poise::FrameworkError<'_, data::Data, Error>) {
@@ -53,7 +54,9 @@ async fn main() {
             commands::register(),
             commands::orange(),
             commands::bestof_cmds::bestof(),
+            commands::bestof_cmds::nominate(),
             commands::quote_cmds::quote(),
+            commands::quote_cmds::save_quote(),
             commands::request_cmds::request(),
             commands::gamenight_cmds::gamenight(),
         ],
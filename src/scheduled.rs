@@ -1,5 +1,6 @@
-use crate::constants::get_update_channel_id;
-use crate::data::bestof::BestOf;
+use crate::constants::{get_update_channel_id, THICC_GUILD_ID};
+use crate::data::archive;
+use crate::data::bestof::{fetch_source_message, post_message_as_embed, BestOf};
 use crate::data::db::BotDatabase;
 use crate::data::quotes::Quotes;
 
@@ -16,16 +17,34 @@ pub async fn spawn_scheduled_tasks(
     bestof: Arc<Mutex<BestOf>>,
     quotes: Arc<Mutex<Quotes>>,
 ) {
-    match load_from_database(db.clone(), bestof.clone()).await {
-        Err(why) => error!("Failed to update from persistent database: {:#?}", why),
-        Ok(_) => info!("Successfully pulled from persistent database!"),
-    }
+    let loaded = match load_from_database(db.clone(), bestof.clone()).await {
+        Err(why) => {
+            error!("Failed to update from persistent database: {:#?}", why);
+            false
+        }
+        Ok(_) => {
+            info!("Successfully pulled from persistent database!");
+            true
+        }
+    };
 
     // Spawn the reaction counting task
     tokio::spawn(search_new_bestof_task(ctx.clone(), bestof.clone()));
 
     // Spawn the persistent database update task
-    tokio::spawn(persistent_database_update_task(db, bestof.clone()));
+    tokio::spawn(persistent_database_update_task(db.clone(), bestof.clone()));
+
+    // Spawn the archived attachment cleanup task, unless not knowing the bestofs would make
+    // every archived attachment look unreferenced
+    if loaded {
+        tokio::spawn(archive_cleanup_task(
+            ctx.clone(),
+            db.clone(),
+            bestof.clone(),
+        ));
+    } else {
+        warn!("Not cleaning up archived attachments, the bestofs failed to load");
+    }
 
     // Spawn the daily bestof posting task
     tokio::spawn(daily_bestof_task(ctx.clone(), bestof));
@@ -128,6 +147,71 @@ async fn persistent_database_update_task(db: Arc<Mutex<BotDatabase>>, bestof: Ar
     }
 }
 
+async fn archive_cleanup_task(
+    ctx: serenity::Context,
+    db: Arc<Mutex<BotDatabase>>,
+    bestof: Arc<Mutex<BestOf>>,
+) {
+    // Leave startup to the tasks that need to catch up
+    sleep(Duration::from_secs(3600)).await;
+
+    loop {
+        // Forget the images of deleted bestofs before deciding which files are unreferenced
+        if let Err(why) = sync_bestofs_with_discord(&ctx, &db, &bestof).await {
+            warn!("Failed to check bestofs against Discord: {:?}", why);
+        }
+
+        // Hold the lock so nothing is archived between collecting references and deleting
+        let bestof_unlocked = bestof.lock().await;
+        let referenced = bestof_unlocked.get_archived_images();
+        match referenced.is_empty() {
+            true => info!("No archived attachments are referenced, skipping cleanup"),
+            false => match archive::remove_unreferenced(&referenced) {
+                Err(why) => warn!("Failed to clean up archived attachments: {:?}", why),
+                Ok(removed) => info!("Removed {:?} unreferenced archived attachments", removed),
+            },
+        }
+        drop(bestof_unlocked);
+
+        let sleep_duration = Duration::from_secs(86400);
+        info!("Next archive cleanup after {:?}", sleep_duration);
+        sleep(sleep_duration).await;
+    }
+}
+
+/// Forget the archived images of bestofs whose message has been deleted from Discord, so the
+/// files are cleaned up. Only bestofs with an archived image are checked.
+async fn sync_bestofs_with_discord(
+    ctx: &serenity::Context,
+    db: &Arc<Mutex<BotDatabase>>,
+    bestof: &Arc<Mutex<BestOf>>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Only hold the lock for each change, checking every message takes a while
+    let messages = bestof.lock().await.get_messages();
+    let mut forgotten = 0;
+
+    for message in messages.iter().filter(|msg| msg.archived_image.is_some()) {
+        match fetch_source_message(ctx, message).await {
+            Err(why) => warn!("Failed to fetch bestof {:?}: {:?}", message.id, why),
+            Ok(None) => {
+                let mut bestof_unlocked = bestof.lock().await;
+                let db_unlocked = db.lock().await;
+                bestof_unlocked
+                    .forget_archived_image(&db_unlocked, message.id)
+                    .await?;
+                forgotten += 1;
+            }
+            Ok(Some(_)) => {}
+        }
+
+        // Go easy on the API, this isn't in a hurry
+        sleep(Duration::from_secs(1)).await;
+    }
+
+    info!("Forgot archived images of {:?} deleted bestofs", forgotten);
+    Ok(())
+}
+
 async fn post_daily_bestof(
     ctx: &serenity::Context,
     bestof: &Arc<Mutex<BestOf>>,
@@ -135,12 +219,20 @@ async fn post_daily_bestof(
     let update_channel = serenity::ChannelId::new(get_update_channel_id());
     let bestof_unlocked = bestof.lock().await;
 
-    let embed = bestof_unlocked.get_random_bestof_embed().await?;
-    let msg = serenity::CreateMessage::new()
-        .embed(embed)
-        .content(String::from("*Here's your daily bestof:*"));
-
-    update_channel.send_message(&ctx.http, msg).await?;
+    let message = bestof_unlocked
+        .get_random_bestof(ctx, update_channel)
+        .await?;
+    let Some(message) = message else {
+        warn!("No bestofs available to post in {:?}", update_channel);
+        return Ok(());
+    };
+    post_message_as_embed(
+        ctx,
+        &message,
+        update_channel,
+        Some(String::from("*Here's your daily bestof:*")),
+    )
+    .await?;
 
     Ok(())
 }
@@ -173,14 +265,23 @@ async fn post_daily_quote(
     quote: &Arc<Mutex<Quotes>>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let update_channel = serenity::ChannelId::new(get_update_channel_id());
+    let guild_id = serenity::GuildId::new(THICC_GUILD_ID);
     let quote_unlocked = quote.lock().await;
 
-    let embed = quote_unlocked.get_random_quote(None).await?.create_embed();
+    let quote = quote_unlocked
+        .get_daily_quote(guild_id)
+        .await?
+        .ok_or("No quotes available")?;
+    let votes = quote_unlocked.get_votes(quote.id).await?;
     let msg = serenity::CreateMessage::new()
-        .embed(embed)
+        .embed(quote.create_embed(ctx).await)
+        .components(vec![votes.create_buttons(quote.id)])
         .content(String::from("*Here's your daily quote:*"));
 
     update_channel.send_message(&ctx.http, msg).await?;
+    quote_unlocked
+        .record_daily_quote(guild_id, quote.id)
+        .await?;
 
     Ok(())
 }